@@ -0,0 +1,194 @@
+//! **Derive macro for the [redo](https://docs.rs/redo) crate.**
+//!
+//! This crate should not be used directly, enable the `derive` feature of `redo` instead.
+
+#![forbid(unsafe_code)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Ident, Type};
+
+/// Derives `Command` for an enum where every variant wraps a single command.
+///
+/// Every method is forwarded to the command in the active variant,
+/// and two commands are only merged if they are in the same variant.
+///
+/// The `Target`, `Error` and `Output` types are taken from the command in the first variant,
+/// and all the other commands must use the same types.
+/// They can also be set explicitly with the `command` attribute:
+///
+/// ```ignore
+/// #[derive(Command)]
+/// #[command(target = String, error = &'static str, output = ())]
+/// enum Edit {
+///     Add(Add),
+///     Pop(Pop),
+/// }
+/// ```
+#[proc_macro_derive(Command, attributes(command))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "`Command` can only be derived for enums",
+            ))
+        }
+    };
+    if data.variants.is_empty() {
+        return Err(Error::new(
+            input.span(),
+            "`Command` can not be derived for an enum without variants",
+        ));
+    }
+
+    let mut variants = Vec::with_capacity(data.variants.len());
+    for variant in &data.variants {
+        match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                variants.push((&variant.ident, &fields.unnamed[0].ty));
+            }
+            _ => {
+                return Err(Error::new(
+                    variant.span(),
+                    "every variant must contain exactly one unnamed field",
+                ))
+            }
+        }
+    }
+
+    let (_, first) = variants[0];
+    let (explicit_target, explicit_error, explicit_output) = parse_attributes(&input)?;
+    let target = match &explicit_target {
+        Some(target) => quote!(#target),
+        None => quote!(<#first as ::redo::Command>::Target),
+    };
+    let error = match &explicit_error {
+        Some(error) => quote!(#error),
+        None => quote!(<#first as ::redo::Command>::Error),
+    };
+    let output = match &explicit_output {
+        Some(output) => quote!(#output),
+        None => quote!(<#first as ::redo::Command>::Output),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote!(where));
+    for (i, (_, ty)) in variants.iter().enumerate() {
+        // The first command can not be bound by its own associated types.
+        let mut assoc = Vec::new();
+        if i > 0 || explicit_target.is_some() {
+            assoc.push(quote!(Target = #target));
+        }
+        if i > 0 || explicit_error.is_some() {
+            assoc.push(quote!(Error = #error));
+        }
+        if i > 0 || explicit_output.is_some() {
+            assoc.push(quote!(Output = #output));
+        }
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#ty: ::redo::Command<#(#assoc),*>));
+    }
+
+    let idents: Vec<&Ident> = variants.iter().map(|(ident, _)| *ident).collect();
+    let forward = |method: Ident| {
+        quote! {
+            match self {
+                #(#name::#idents(command) => ::redo::Command::#method(command, target),)*
+            }
+        }
+    };
+    let apply = forward(Ident::new("apply", name.span()));
+    let undo = forward(Ident::new("undo", name.span()));
+    let redo = forward(Ident::new("redo", name.span()));
+    let heap_size = quote! {
+        match self {
+            #(#name::#idents(command) => ::redo::Command::heap_size(command),)*
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::redo::Command for #name #ty_generics #where_clause {
+            type Target = #target;
+            type Error = #error;
+            type Output = #output;
+
+            fn apply(
+                &mut self,
+                target: &mut Self::Target,
+            ) -> ::core::result::Result<Self::Output, Self::Error> {
+                #apply
+            }
+
+            fn undo(&mut self, target: &mut Self::Target) -> ::redo::Result<Self> {
+                #undo
+            }
+
+            fn redo(&mut self, target: &mut Self::Target) -> ::redo::Result<Self> {
+                #redo
+            }
+
+            fn merge(&mut self, command: Self) -> ::redo::Merge<Self> {
+                match (self, command) {
+                    #((#name::#idents(this), #name::#idents(command)) => {
+                        match ::redo::Command::merge(this, command) {
+                            ::redo::Merge::Yes => ::redo::Merge::Yes,
+                            ::redo::Merge::No(command) => ::redo::Merge::No(#name::#idents(command)),
+                            ::redo::Merge::Annul => ::redo::Merge::Annul,
+                        }
+                    })*
+                    #[allow(unreachable_patterns)]
+                    (_, command) => ::redo::Merge::No(command),
+                }
+            }
+
+            fn heap_size(&self) -> usize {
+                #heap_size
+            }
+        }
+    })
+}
+
+type Attributes = (Option<Type>, Option<Type>, Option<Type>);
+
+fn parse_attributes(input: &DeriveInput) -> syn::Result<Attributes> {
+    let mut target = None;
+    let mut error = None;
+    let mut output = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("command"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("target") {
+                target = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("error") {
+                error = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("output") {
+                output = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `target`, `error` or `output`"))
+            }
+        })?;
+    }
+    Ok((target, error, output))
+}
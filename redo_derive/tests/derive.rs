@@ -0,0 +1,83 @@
+use redo::{Command, Merge, Record};
+
+struct Add(char);
+
+impl Command for Add {
+    type Target = String;
+    type Error = &'static str;
+    type Output = ();
+
+    fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
+        s.push(self.0);
+        Ok(())
+    }
+
+    fn undo(&mut self, s: &mut String) -> redo::Result<Add> {
+        self.0 = s.pop().ok_or("s is empty")?;
+        Ok(())
+    }
+}
+
+struct Push(String);
+
+impl Command for Push {
+    type Target = String;
+    type Error = &'static str;
+    type Output = ();
+
+    fn apply(&mut self, s: &mut String) -> redo::Result<Push> {
+        s.push_str(&self.0);
+        Ok(())
+    }
+
+    fn undo(&mut self, s: &mut String) -> redo::Result<Push> {
+        let len = s.len().checked_sub(self.0.len()).ok_or("s is too short")?;
+        s.truncate(len);
+        Ok(())
+    }
+
+    fn merge(&mut self, Push(s): Self) -> Merge<Self> {
+        self.0.push_str(&s);
+        Merge::Yes
+    }
+}
+
+#[derive(Command)]
+enum Edit {
+    Add(Add),
+    Push(Push),
+}
+
+#[derive(Command)]
+#[command(target = String, error = &'static str)]
+enum Explicit {
+    Push(Push),
+}
+
+#[test]
+fn forward() {
+    let mut record = redo::record::Builder::new().saved(false).default();
+    record.apply(Edit::Add(Add('a'))).unwrap();
+    record.apply(Edit::Push(Push("bc".into()))).unwrap();
+    record.apply(Edit::Push(Push("de".into()))).unwrap();
+    assert_eq!(record.target(), "abcde");
+    assert_eq!(record.len(), 2);
+    record.apply(Edit::Add(Add('f'))).unwrap();
+    record.apply(Edit::Push(Push("g".into()))).unwrap();
+    assert_eq!(record.len(), 4);
+    record.undo().unwrap();
+    record.undo().unwrap();
+    record.undo().unwrap();
+    assert_eq!(record.target(), "a");
+    record.redo().unwrap();
+    assert_eq!(record.target(), "abcde");
+}
+
+#[test]
+fn explicit() {
+    let mut record = Record::default();
+    record.apply(Explicit::Push(Push("ab".into()))).unwrap();
+    assert_eq!(record.target(), "ab");
+    record.undo().unwrap();
+    assert_eq!(record.target(), "");
+}
@@ -0,0 +1,274 @@
+use crate::{Command, Merge};
+use alloc::boxed::Box;
+use core::{any::Any, fmt};
+
+/// An object safe version of [`Command`].
+///
+/// This trait is implemented for all commands that are `'static`, and is used by
+/// [`Boxed`] to store commands of different types in the same record or history.
+///
+/// [`Command`]: trait.Command.html
+/// [`Boxed`]: struct.Boxed.html
+pub trait DynCommand: Any {
+    /// The target type.
+    type Target;
+    /// The error type.
+    type Error;
+    /// The output type.
+    type Output;
+
+    /// Calls the [`apply`] method of the command.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    fn dyn_apply(&mut self, target: &mut Self::Target) -> Result<Self::Output, Self::Error>;
+
+    /// Calls the [`undo`] method of the command.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    fn dyn_undo(&mut self, target: &mut Self::Target) -> Result<(), Self::Error>;
+
+    /// Calls the [`redo`] method of the command.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    fn dyn_redo(&mut self, target: &mut Self::Target) -> Result<(), Self::Error>;
+
+    /// Calls the [`merge`] method of the command if `command` has the same type,
+    /// otherwise `command` is returned unmerged.
+    ///
+    /// [`merge`]: trait.Command.html#method.merge
+    fn dyn_merge(
+        &mut self,
+        command: Boxed<Self::Target, Self::Error, Self::Output>,
+    ) -> Merge<Boxed<Self::Target, Self::Error, Self::Output>>;
+
+    /// Calls the [`heap_size`] method of the command.
+    ///
+    /// [`heap_size`]: trait.Command.html#method.heap_size
+    fn dyn_heap_size(&self) -> usize;
+
+    /// Returns the command as `Any`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Converts the boxed command into a boxed `Any`.
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<C: Command + 'static> DynCommand for C {
+    type Target = C::Target;
+    type Error = C::Error;
+    type Output = C::Output;
+
+    fn dyn_apply(&mut self, target: &mut C::Target) -> Result<C::Output, C::Error> {
+        self.apply(target)
+    }
+
+    fn dyn_undo(&mut self, target: &mut C::Target) -> Result<(), C::Error> {
+        self.undo(target)
+    }
+
+    fn dyn_redo(&mut self, target: &mut C::Target) -> Result<(), C::Error> {
+        self.redo(target)
+    }
+
+    fn dyn_merge(
+        &mut self,
+        command: Boxed<C::Target, C::Error, C::Output>,
+    ) -> Merge<Boxed<C::Target, C::Error, C::Output>> {
+        if !(*command.command).as_any().is::<C>() {
+            return Merge::No(command);
+        }
+        let command = *command.command.into_any().downcast::<C>().unwrap();
+        match self.merge(command) {
+            Merge::Yes => Merge::Yes,
+            Merge::No(command) => Merge::No(Boxed::new(command)),
+            Merge::Annul => Merge::Annul,
+        }
+    }
+
+    fn dyn_heap_size(&self) -> usize {
+        self.heap_size()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// A boxed command that uses dynamic dispatch.
+///
+/// Allows commands of different types to be stored in the same record or history,
+/// as long as they have the same target, error and output type.
+/// Two commands are only merged if they have the same type.
+///
+/// # Examples
+/// ```
+/// # use redo::{Boxed, Command, Record};
+/// struct Add(char);
+///
+/// impl Command for Add {
+///     type Target = String;
+///     type Error = &'static str;
+///     type Output = ();
+///
+///     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
+///         s.push(self.0);
+///         Ok(())
+///     }
+///
+///     fn undo(&mut self, s: &mut String) -> redo::Result<Add> {
+///         self.0 = s.pop().ok_or("s is empty")?;
+///         Ok(())
+///     }
+/// }
+///
+/// struct Clear(String);
+///
+/// impl Command for Clear {
+///     type Target = String;
+///     type Error = &'static str;
+///     type Output = ();
+///
+///     fn apply(&mut self, s: &mut String) -> redo::Result<Clear> {
+///         self.0 = core::mem::take(s);
+///         Ok(())
+///     }
+///
+///     fn undo(&mut self, s: &mut String) -> redo::Result<Clear> {
+///         *s = core::mem::take(&mut self.0);
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
+/// let mut record = Record::default();
+/// record.apply(Boxed::new(Add('a')))?;
+/// record.apply(Boxed::new(Add('b')))?;
+/// record.apply(Boxed::new(Clear(String::new())))?;
+/// assert_eq!(record.target(), "");
+/// record.undo()?;
+/// assert_eq!(record.target(), "ab");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Boxed<T: 'static, E: 'static, O: 'static = ()> {
+    command: Box<dyn DynCommand<Target = T, Error = E, Output = O>>,
+}
+
+impl<T: 'static, E: 'static, O: 'static> Boxed<T, E, O> {
+    /// Returns a new boxed command.
+    pub fn new<C>(command: C) -> Boxed<T, E, O>
+    where
+        C: Command<Target = T, Error = E, Output = O> + 'static,
+    {
+        Boxed {
+            command: Box::new(command),
+        }
+    }
+
+    /// Returns `true` if the boxed command is of type `C`.
+    pub fn is<C: Command<Target = T, Error = E, Output = O> + 'static>(&self) -> bool {
+        (*self.command).as_any().is::<C>()
+    }
+
+    /// Returns a reference to the boxed command if it is of type `C`.
+    pub fn downcast_ref<C: Command<Target = T, Error = E, Output = O> + 'static>(
+        &self,
+    ) -> Option<&C> {
+        (*self.command).as_any().downcast_ref()
+    }
+}
+
+impl<T: 'static, E: 'static, O: 'static> Command for Boxed<T, E, O> {
+    type Target = T;
+    type Error = E;
+    type Output = O;
+
+    fn apply(&mut self, target: &mut T) -> Result<O, E> {
+        self.command.dyn_apply(target)
+    }
+
+    fn undo(&mut self, target: &mut T) -> Result<(), E> {
+        self.command.dyn_undo(target)
+    }
+
+    fn redo(&mut self, target: &mut T) -> Result<(), E> {
+        self.command.dyn_redo(target)
+    }
+
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        self.command.dyn_merge(command)
+    }
+
+    fn heap_size(&self) -> usize {
+        self.command.dyn_heap_size()
+    }
+}
+
+impl<T: 'static, E: 'static, O: 'static> fmt::Debug for Boxed<T, E, O> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Boxed { .. }")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use alloc::string::String;
+
+    struct Add(char);
+
+    impl Command for Add {
+        type Target = String;
+        type Error = &'static str;
+        type Output = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+
+        fn merge(&mut self, _: Self) -> Merge<Self> {
+            Merge::Yes
+        }
+    }
+
+    struct Pop(Option<char>);
+
+    impl Command for Pop {
+        type Target = String;
+        type Error = &'static str;
+        type Output = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<Pop> {
+            self.0 = s.pop();
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Pop> {
+            s.extend(self.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let mut record = record::Builder::new().saved(false).default();
+        record.apply(Boxed::new(Pop(None))).unwrap();
+        record.apply(Boxed::new(Add('a'))).unwrap();
+        record.apply(Boxed::new(Add('b'))).unwrap();
+        assert_eq!(record.len(), 2);
+        assert!(record.entries[0].command.is::<Pop>());
+        assert!(record.entries[1].command.is::<Add>());
+        record.apply(Boxed::new(Pop(None))).unwrap();
+        assert_eq!(record.len(), 3);
+    }
+}
@@ -0,0 +1,302 @@
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use core::{fmt, ops::Add, time::Duration};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "time")]
+use {
+    core::convert::TryFrom,
+    time::{
+        format_description::{
+            parse_strftime_borrowed,
+            well_known::{Rfc2822, Rfc3339},
+        },
+        OffsetDateTime, UtcOffset,
+    },
+};
+
+/// A timestamp of a command.
+///
+/// Timestamps are used for time travel, for the max age and merge window of the records
+/// and histories, and are shown by the display structures.
+pub trait Timestamp: Copy + Ord {
+    /// Returns the timestamp that time is counted from, like the Unix epoch.
+    ///
+    /// Used for the commands of data that was serialized without timestamps.
+    fn epoch() -> Self;
+
+    /// Returns the duration from `earlier` to `self`,
+    /// or a zero duration if `earlier` is later than `self`.
+    fn duration_since(&self, earlier: &Self) -> Duration;
+
+    /// Formats the timestamp for the display structures.
+    ///
+    /// The default implementation writes nothing.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let _ = f;
+        Ok(())
+    }
+
+    /// Formats the timestamp for the display structures with the strftime-style `pattern`,
+    /// or in the same format as `fmt` if there is no pattern.
+    /// The timestamp is shown in UTC if `utc` is `true` and otherwise in the local time zone.
+    ///
+    /// The default implementation ignores the pattern and the time zone and uses `fmt`.
+    fn fmt_with(&self, f: &mut fmt::Formatter, pattern: Option<&str>, utc: bool) -> fmt::Result {
+        let _ = (pattern, utc);
+        self.fmt(f)
+    }
+
+    /// Writes the timestamp as a JSON value for the JSON export.
+    ///
+    /// The default implementation writes `null`.
+    fn fmt_json(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("null")
+    }
+}
+
+/// No timestamp. All durations are zero, so commands never expire and are always in the merge window.
+impl Timestamp for () {
+    fn epoch() {}
+
+    fn duration_since(&self, _: &()) -> Duration {
+        Duration::from_secs(0)
+    }
+}
+
+/// The time since some starting point, like the ticks of a monotonic timer.
+impl Timestamp for Duration {
+    fn epoch() -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn duration_since(&self, earlier: &Duration) -> Duration {
+        self.checked_sub(*earlier).unwrap_or_default()
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+
+    /// Written as the number of nanoseconds.
+    fn fmt_json(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_nanos())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Timestamp for DateTime<Utc> {
+    fn epoch() -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH
+    }
+
+    fn duration_since(&self, earlier: &DateTime<Utc>) -> Duration {
+        self.signed_duration_since(*earlier)
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.with_timezone(&Local).to_rfc2822())
+    }
+
+    fn fmt_with(&self, f: &mut fmt::Formatter, pattern: Option<&str>, utc: bool) -> fmt::Result {
+        match (pattern, utc) {
+            (Some(pattern), true) => write!(f, "{}", self.format(pattern)),
+            (Some(pattern), false) => write!(f, "{}", self.with_timezone(&Local).format(pattern)),
+            (None, true) => f.write_str(&self.to_rfc2822()),
+            (None, false) => Timestamp::fmt(self, f),
+        }
+    }
+
+    /// Written as an RFC 3339 string in UTC.
+    fn fmt_json(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\"",
+            self.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+        )
+    }
+}
+
+/// Formatted as RFC 2822 in the local time zone, like the chrono timestamps.
+///
+/// The offset of the timestamp is used instead if the local offset can not be determined.
+#[cfg(feature = "time")]
+impl Timestamp for OffsetDateTime {
+    fn epoch() -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH
+    }
+
+    fn duration_since(&self, earlier: &OffsetDateTime) -> Duration {
+        Duration::try_from(*self - *earlier).unwrap_or_default()
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(f, None, false)
+    }
+
+    fn fmt_with(&self, f: &mut fmt::Formatter, pattern: Option<&str>, utc: bool) -> fmt::Result {
+        let offset = if utc {
+            UtcOffset::UTC
+        } else {
+            UtcOffset::current_local_offset().unwrap_or_else(|_| self.offset())
+        };
+        let datetime = self.to_offset(offset);
+        let formatted = match pattern {
+            Some(pattern) => {
+                let items = parse_strftime_borrowed(pattern).map_err(|_| fmt::Error)?;
+                datetime.format(&items)
+            }
+            None => datetime.format(&Rfc2822),
+        };
+        f.write_str(&formatted.map_err(|_| fmt::Error)?)
+    }
+
+    /// Written as an RFC 3339 string in UTC.
+    fn fmt_json(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let formatted = self.to_offset(UtcOffset::UTC).format(&Rfc3339);
+        write!(f, "\"{}\"", formatted.map_err(|_| fmt::Error)?)
+    }
+}
+
+/// A clock that supplies the timestamps of the commands.
+///
+/// The records and histories ask the clock for the time when a command is applied or merged.
+pub trait Clock {
+    /// The timestamp type.
+    type Timestamp: Timestamp;
+
+    /// Returns the current time.
+    fn now(&mut self) -> Self::Timestamp;
+}
+
+/// The clock of the records and histories that do not use timestamps.
+impl Clock for () {
+    type Timestamp = ();
+
+    fn now(&mut self) {}
+}
+
+/// The clock used by default.
+///
+/// This is [`ChronoClock`] if the `chrono` feature is enabled, `TimeClock` if only the `time`
+/// feature is enabled, and otherwise `()` which does not use timestamps.
+///
+/// [`ChronoClock`]: struct.ChronoClock.html
+#[cfg(feature = "chrono")]
+pub type DefaultClock = ChronoClock;
+
+/// The clock used by default.
+///
+/// This is `ChronoClock` if the `chrono` feature is enabled, [`TimeClock`] if only the `time`
+/// feature is enabled, and otherwise `()` which does not use timestamps.
+///
+/// [`TimeClock`]: struct.TimeClock.html
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub type DefaultClock = TimeClock;
+
+/// The clock used by default.
+///
+/// This is `ChronoClock` if the `chrono` feature is enabled, `TimeClock` if only the `time`
+/// feature is enabled, and otherwise `()` which does not use timestamps.
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+pub type DefaultClock = ();
+
+/// A clock that reads the system time by using [chrono](https://docs.rs/chrono).
+#[cfg(feature = "chrono")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct ChronoClock;
+
+#[cfg(feature = "chrono")]
+impl Clock for ChronoClock {
+    type Timestamp = DateTime<Utc>;
+
+    fn now(&mut self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that reads the system time in UTC by using [time](https://docs.rs/time).
+#[cfg(feature = "time")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct TimeClock;
+
+#[cfg(feature = "time")]
+impl Clock for TimeClock {
+    type Timestamp = OffsetDateTime;
+
+    fn now(&mut self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock that is only moved by hand.
+///
+/// Useful for testing time based behavior deterministically, and in `no_std` builds
+/// where the time can be read from a monotonic timer and set on the clock.
+///
+/// # Examples
+/// ```
+/// # use core::time::Duration;
+/// # use redo::{record::Builder, Command, ManualClock};
+/// # struct Add(char);
+/// # impl Command for Add {
+/// #     type Target = String;
+/// #     type Error = &'static str;
+/// #     type Output = ();
+/// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> redo::Result<Add> {
+/// #         self.0 = s.pop().ok_or("s is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
+/// let mut record = Builder::new()
+///     .clock(ManualClock::new(Duration::from_secs(0)))
+///     .default();
+/// record.apply(Add('a'))?;
+/// record.clock_mut().advance(Duration::from_secs(1));
+/// record.apply(Add('b'))?;
+/// record.time_travel(&Duration::from_secs(1)).unwrap()?;
+/// assert_eq!(record.target(), "a");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct ManualClock<T = Duration> {
+    now: T,
+}
+
+impl<T> ManualClock<T> {
+    /// Returns a new clock that is set to `now`.
+    pub fn new(now: T) -> ManualClock<T> {
+        ManualClock { now }
+    }
+
+    /// Sets the time of the clock.
+    pub fn set(&mut self, now: T) {
+        self.now = now;
+    }
+}
+
+impl<T: Copy + Add<Duration, Output = T>> ManualClock<T> {
+    /// Moves the time of the clock forward by `duration`.
+    pub fn advance(&mut self, duration: Duration) {
+        self.now = self.now + duration;
+    }
+}
+
+impl<T: Timestamp> Clock for ManualClock<T> {
+    type Timestamp = T;
+
+    fn now(&mut self) -> T {
+        self.now
+    }
+}
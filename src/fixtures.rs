@@ -0,0 +1,87 @@
+//! Commands shared by the tests of the records and histories.
+
+use crate::{Command, Merge, Result};
+use alloc::string::String;
+
+/// Edits the text of the target.
+pub(crate) enum Edit {
+    /// Appends the text.
+    Push(String),
+    /// Appends the text, and is merged into a previous `Type`.
+    Type(String),
+    /// Removes the text from the end, and annuls a previous `Push` of the same text.
+    Pop(String),
+}
+
+impl Edit {
+    fn text(&self) -> &str {
+        match self {
+            Edit::Push(text) | Edit::Type(text) | Edit::Pop(text) => text,
+        }
+    }
+}
+
+impl Command for Edit {
+    type Target = String;
+    type Error = &'static str;
+    type Output = usize;
+
+    fn apply(&mut self, s: &mut String) -> core::result::Result<usize, &'static str> {
+        match self {
+            Edit::Push(text) | Edit::Type(text) => s.push_str(text),
+            Edit::Pop(text) => pop(s, text)?,
+        }
+        Ok(s.len())
+    }
+
+    fn undo(&mut self, s: &mut String) -> Result<Edit> {
+        match self {
+            Edit::Push(text) | Edit::Type(text) => pop(s, text),
+            Edit::Pop(text) => {
+                s.push_str(text);
+                Ok(())
+            }
+        }
+    }
+
+    fn merge(&mut self, edit: Self) -> Merge<Self> {
+        match (self, edit) {
+            (Edit::Type(text), Edit::Type(more)) => {
+                text.push_str(&more);
+                Merge::Yes
+            }
+            (Edit::Push(text), Edit::Pop(other)) if *text == other => Merge::Annul,
+            (_, edit) => Merge::No(edit),
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        self.text().len()
+    }
+}
+
+fn pop(s: &mut String, text: &str) -> core::result::Result<(), &'static str> {
+    if !s.ends_with(text) {
+        return Err("s does not end with the text");
+    }
+    s.truncate(s.len() - text.len());
+    Ok(())
+}
+
+/// Uses up one unit of the fuel in the target each time it is called.
+pub(crate) struct Spend;
+
+impl Command for Spend {
+    type Target = usize;
+    type Error = &'static str;
+    type Output = ();
+
+    fn apply(&mut self, fuel: &mut usize) -> Result<Spend> {
+        *fuel = fuel.checked_sub(1).ok_or("out of fuel")?;
+        Ok(())
+    }
+
+    fn undo(&mut self, fuel: &mut usize) -> Result<Spend> {
+        self.apply(fuel)
+    }
+}
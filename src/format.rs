@@ -1,40 +1,237 @@
 #![cfg_attr(not(feature = "colored"), allow(unused_variables))]
 
-use crate::At;
-use alloc::string::ToString;
-#[cfg(feature = "chrono")]
-use chrono::{DateTime, Local, Utc};
-use core::fmt::{self, Write};
+use crate::{At, Entry, Timestamp};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
 #[cfg(feature = "colored")]
-use {
-    alloc::format,
-    colored::{Color, Colorize},
+use colored::{Color, Colorize};
+use core::{
+    fmt::{self, Write},
+    ops::{Bound, RangeBounds},
+    time::Duration,
 };
 
+/// The symbols, labels and colors used by the display structures.
+///
+/// The default style draws the graph of the history with `*`, `|` and `/`.
+/// The symbols, labels and palette are borrowed, so they can be built at runtime.
+/// The colors are only used if the `colored` feature is enabled.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, History, Style};
+/// # struct Add(char);
+/// # impl std::fmt::Display for Add {
+/// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// #         write!(f, "Add {}", self.0)
+/// #     }
+/// # }
+/// # impl Command for Add {
+/// #     type Target = String;
+/// #     type Error = &'static str;
+/// #     type Output = ();
+/// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> redo::Result<Add> {
+/// #         self.0 = s.pop().ok_or("s is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
+/// let mut style = Style::default();
+/// style.mark("●").edge("│").fork("╯").current_label("here");
+/// let mut history = History::default();
+/// history.apply(Add('a'))?;
+/// println!("{}", history.display().style(style));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Style<'a> {
+    mark: &'a str,
+    edge: &'a str,
+    fork: &'a str,
+    current_label: &'a str,
+    saved_label: &'a str,
+    #[cfg(feature = "colored")]
+    palette: &'a [Color],
+    #[cfg(feature = "colored")]
+    position_color: Color,
+    #[cfg(feature = "colored")]
+    current_color: Color,
+    #[cfg(feature = "colored")]
+    saved_color: Color,
+    #[cfg(feature = "colored")]
+    name_color: Color,
+    #[cfg(feature = "colored")]
+    timestamp_color: Color,
+    #[cfg(feature = "colored")]
+    delimiter_color: Color,
+}
+
+impl<'a> Style<'a> {
+    /// Sets the symbol that marks a command in the graph.
+    pub fn mark(&mut self, mark: &'a str) -> &mut Style<'a> {
+        self.mark = mark;
+        self
+    }
+
+    /// Sets the symbol that connects the commands in the graph.
+    pub fn edge(&mut self, edge: &'a str) -> &mut Style<'a> {
+        self.edge = edge;
+        self
+    }
+
+    /// Sets the symbol that is drawn after an edge where a branch is forked off.
+    pub fn fork(&mut self, fork: &'a str) -> &mut Style<'a> {
+        self.fork = fork;
+        self
+    }
+
+    /// Sets the label of the current position.
+    pub fn current_label(&mut self, label: &'a str) -> &mut Style<'a> {
+        self.current_label = label;
+        self
+    }
+
+    /// Sets the label of the saved position.
+    pub fn saved_label(&mut self, label: &'a str) -> &mut Style<'a> {
+        self.saved_label = label;
+        self
+    }
+
+    /// Sets the colors of the levels in the graph, which are repeated for deeper levels.
+    ///
+    /// The graph is not colored if `palette` is empty.
+    #[cfg(feature = "colored")]
+    pub fn palette(&mut self, palette: &'a [Color]) -> &mut Style<'a> {
+        self.palette = palette;
+        self
+    }
+
+    /// Sets the color of the positions.
+    #[cfg(feature = "colored")]
+    pub fn position_color(&mut self, color: Color) -> &mut Style<'a> {
+        self.position_color = color;
+        self
+    }
+
+    /// Sets the color of the current label.
+    #[cfg(feature = "colored")]
+    pub fn current_color(&mut self, color: Color) -> &mut Style<'a> {
+        self.current_color = color;
+        self
+    }
+
+    /// Sets the color of the saved label.
+    #[cfg(feature = "colored")]
+    pub fn saved_color(&mut self, color: Color) -> &mut Style<'a> {
+        self.saved_color = color;
+        self
+    }
+
+    /// Sets the color of the branch names and bookmarks.
+    #[cfg(feature = "colored")]
+    pub fn name_color(&mut self, color: Color) -> &mut Style<'a> {
+        self.name_color = color;
+        self
+    }
+
+    /// Sets the color of the timestamps.
+    #[cfg(feature = "colored")]
+    pub fn timestamp_color(&mut self, color: Color) -> &mut Style<'a> {
+        self.timestamp_color = color;
+        self
+    }
+
+    /// Sets the color of the parentheses, brackets and commas around the labels and names.
+    #[cfg(feature = "colored")]
+    pub fn delimiter_color(&mut self, color: Color) -> &mut Style<'a> {
+        self.delimiter_color = color;
+        self
+    }
+}
+
+impl Default for Style<'_> {
+    fn default() -> Self {
+        Style {
+            mark: "*",
+            edge: "|",
+            fork: "/",
+            current_label: "current",
+            saved_label: "saved",
+            #[cfg(feature = "colored")]
+            palette: &[
+                Color::Cyan,
+                Color::Red,
+                Color::Magenta,
+                Color::Yellow,
+                Color::Green,
+                Color::Blue,
+            ],
+            #[cfg(feature = "colored")]
+            position_color: Color::Yellow,
+            #[cfg(feature = "colored")]
+            current_color: Color::Cyan,
+            #[cfg(feature = "colored")]
+            saved_color: Color::Green,
+            #[cfg(feature = "colored")]
+            name_color: Color::Magenta,
+            #[cfg(feature = "colored")]
+            timestamp_color: Color::Yellow,
+            #[cfg(feature = "colored")]
+            delimiter_color: Color::Yellow,
+        }
+    }
+}
+
+/// The parts of the output that are painted in different colors.
+#[cfg_attr(not(feature = "colored"), allow(dead_code))]
+#[derive(Copy, Clone)]
+enum Paint {
+    Level(usize),
+    Position,
+    Current,
+    Saved,
+    Name,
+    Timestamp,
+    Delimiter,
+}
+
 #[derive(Copy, Clone, Debug)]
-pub(crate) struct Format {
+pub(crate) struct Format<'a> {
     #[cfg(feature = "colored")]
     pub colored: bool,
     pub current: bool,
     pub detailed: bool,
+    pub names: bool,
     pub position: bool,
     pub saved: bool,
+    pub style: Style<'a>,
+    pub range: (usize, usize),
 }
 
-impl Default for Format {
+impl Default for Format<'_> {
     fn default() -> Self {
         Format {
             #[cfg(feature = "colored")]
             colored: true,
             current: true,
             detailed: true,
+            names: true,
             position: true,
             saved: true,
+            style: Style::default(),
+            range: (0, usize::MAX),
         }
     }
 }
 
-impl Format {
+impl Format<'_> {
     pub fn message(
         self,
         f: &mut fmt::Formatter,
@@ -54,70 +251,63 @@ impl Format {
                 writeln!(f, "{}", line.trim())?;
             }
         } else if let Some(line) = lines.map(str::trim).find(|s| !s.is_empty()) {
-            f.write_str(&line)?;
+            f.write_str(line)?;
         }
         Ok(())
     }
 
+    pub fn is_visible(self, at: At) -> bool {
+        self.range.0 <= at.current && at.current < self.range.1
+    }
+
+    pub fn set_range(&mut self, range: impl RangeBounds<usize>) {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => usize::MAX,
+        };
+        self.range = (start, end);
+    }
+
+    pub fn set_window(&mut self, current: usize, n: usize) {
+        self.set_range(current.saturating_sub(n)..=current.saturating_add(n));
+    }
+
+    pub fn elided(self, f: &mut fmt::Formatter, n: usize) -> fmt::Result {
+        self.paint(
+            f,
+            &format!("\u{2026} {} more \u{2026}", n),
+            Paint::Delimiter,
+        )
+    }
+
     pub fn mark(self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
-        #[cfg(feature = "colored")]
-        if self.colored {
-            write!(f, "{} ", "*".color(color_of_level(level)))
-        } else {
-            f.write_str("* ")
-        }
-        #[cfg(not(feature = "colored"))]
-        f.write_str("* ")
+        self.paint(f, self.style.mark, Paint::Level(level))?;
+        f.write_char(' ')
     }
 
     pub fn edge(self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
-        #[cfg(feature = "colored")]
-        if self.colored {
-            write!(f, "{}", "|".color(color_of_level(level)))
-        } else {
-            f.write_char('|')
-        }
-        #[cfg(not(feature = "colored"))]
-        f.write_char('|')
+        self.paint(f, self.style.edge, Paint::Level(level))
     }
 
     pub fn split(self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
-        #[cfg(feature = "colored")]
-        if self.colored {
-            write!(
-                f,
-                "{}{}",
-                "|".color(color_of_level(level)),
-                "/".color(color_of_level(level + 1))
-            )
-        } else {
-            f.write_str("|/")
-        }
-        #[cfg(not(feature = "colored"))]
-        f.write_str("|/")
+        self.paint(f, self.style.edge, Paint::Level(level))?;
+        self.paint(f, self.style.fork, Paint::Level(level + 1))
     }
 
     pub fn position(self, f: &mut fmt::Formatter, at: At, use_branch: bool) -> fmt::Result {
         if self.position {
-            #[cfg(feature = "colored")]
-            if self.colored {
-                let position = if use_branch {
-                    format!("{}:{}", at.branch, at.current)
-                } else {
-                    format!("{}", at.current)
-                };
-                write!(f, "{}", position.yellow().bold())
-            } else if use_branch {
-                write!(f, "{}:{}", at.branch, at.current)
-            } else {
-                write!(f, "{}", at.current)
-            }
-            #[cfg(not(feature = "colored"))]
-            if use_branch {
-                write!(f, "{}:{}", at.branch, at.current)
+            let position = if use_branch {
+                format!("{}:{}", at.branch, at.current)
             } else {
-                write!(f, "{}", at.current)
-            }
+                at.current.to_string()
+            };
+            self.paint(f, &position, Paint::Position)
         } else {
             Ok(())
         }
@@ -130,87 +320,182 @@ impl Format {
         current: At,
         saved: Option<At>,
     ) -> fmt::Result {
-        match (
-            self.current && at == current,
-            self.saved && saved.map_or(false, |saved| saved == at),
-        ) {
-            (true, true) => {
-                #[cfg(feature = "colored")]
-                if self.colored {
-                    write!(
-                        f,
-                        " {}{}{} {}{}",
-                        "(".yellow(),
-                        "current".cyan().bold(),
-                        ",".yellow(),
-                        "saved".green().bold(),
-                        ")".yellow()
-                    )
-                } else {
-                    f.write_str(" (current, saved)")
-                }
-                #[cfg(not(feature = "colored"))]
-                f.write_str(" (current)")
-            }
-            (true, false) => {
-                #[cfg(feature = "colored")]
-                if self.colored {
-                    write!(
-                        f,
-                        " {}{}{}",
-                        "(".yellow(),
-                        "current".cyan().bold(),
-                        ")".yellow()
-                    )
-                } else {
-                    f.write_str(" (current)")
-                }
-                #[cfg(not(feature = "colored"))]
-                f.write_str(" (current)")
+        let current = self.current && at == current;
+        let saved = self.saved && saved == Some(at);
+        if !current && !saved {
+            return Ok(());
+        }
+        f.write_char(' ')?;
+        self.paint(f, "(", Paint::Delimiter)?;
+        if current {
+            self.paint(f, self.style.current_label, Paint::Current)?;
+        }
+        if current && saved {
+            self.paint(f, ",", Paint::Delimiter)?;
+            f.write_char(' ')?;
+        }
+        if saved {
+            self.paint(f, self.style.saved_label, Paint::Saved)?;
+        }
+        self.paint(f, ")", Paint::Delimiter)
+    }
+
+    pub fn name(self, f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+        f.write_char(' ')?;
+        self.paint(f, "[", Paint::Delimiter)?;
+        self.paint(f, name, Paint::Name)?;
+        self.paint(f, "]", Paint::Delimiter)
+    }
+
+    pub fn timestamp<T: Timestamp>(
+        self,
+        f: &mut fmt::Formatter,
+        timestamp: &Formatted<'_, T>,
+    ) -> fmt::Result {
+        let timestamp = timestamp.format();
+        if timestamp.is_empty() {
+            return Ok(());
+        }
+        f.write_char(' ')?;
+        self.paint(f, &timestamp, Paint::Timestamp)
+    }
+
+    #[cfg(feature = "colored")]
+    fn paint(self, f: &mut fmt::Formatter, s: &str, paint: Paint) -> fmt::Result {
+        if !self.colored {
+            return f.write_str(s);
+        }
+        let style = &self.style;
+        match paint {
+            Paint::Level(level) => match level.checked_rem(style.palette.len()) {
+                Some(i) => write!(f, "{}", s.color(style.palette[i])),
+                None => f.write_str(s),
+            },
+            Paint::Position => write!(f, "{}", s.color(style.position_color).bold()),
+            Paint::Current => write!(f, "{}", s.color(style.current_color).bold()),
+            Paint::Saved => write!(f, "{}", s.color(style.saved_color).bold()),
+            Paint::Name => write!(f, "{}", s.color(style.name_color).bold()),
+            Paint::Timestamp => write!(f, "{}", s.color(style.timestamp_color)),
+            Paint::Delimiter => write!(f, "{}", s.color(style.delimiter_color)),
+        }
+    }
+
+    #[cfg(not(feature = "colored"))]
+    fn paint(self, f: &mut fmt::Formatter, s: &str, paint: Paint) -> fmt::Result {
+        f.write_str(s)
+    }
+}
+
+/// How the timestamps are formatted by the display structures.
+#[derive(Copy, Clone)]
+pub(crate) enum TimeFormat<'a, T> {
+    /// Formatted by using `Timestamp::fmt`, or `Timestamp::fmt_with` if the time zone is set.
+    Default,
+    /// Formatted by using the function.
+    Function(fn(&T, &mut fmt::Formatter) -> fmt::Result),
+    /// Formatted by using `Timestamp::fmt_with` with the strftime-style pattern.
+    Pattern(&'a str),
+    /// Formatted as the time elapsed until now.
+    Relative(T),
+}
+
+/// Displays a timestamp in the time format.
+pub(crate) struct Formatted<'a, T> {
+    pub timestamp: &'a T,
+    pub format: TimeFormat<'a, T>,
+    pub utc: Option<bool>,
+}
+
+impl<T: Timestamp> Formatted<'_, T> {
+    /// Returns the formatted timestamp, which is empty if the timestamp is formatted as nothing.
+    ///
+    /// If the timestamp can not be formatted, like when the pattern is not valid,
+    /// the default format is used instead so the rest of the output is still written.
+    pub fn format(&self) -> String {
+        let default = Formatted {
+            timestamp: self.timestamp,
+            format: TimeFormat::Default,
+            utc: self.utc,
+        };
+        let mut s = String::new();
+        if let TimeFormat::Relative(_) = self.format {
+            // Timestamps without a time, like `()`, are not shown as relative either.
+            if write!(s, "{}", default).is_ok() && s.is_empty() {
+                return s;
             }
-            (false, true) => {
-                #[cfg(feature = "colored")]
-                if self.colored {
-                    write!(
-                        f,
-                        " {}{}{}",
-                        "(".yellow(),
-                        "saved".green().bold(),
-                        ")".yellow()
-                    )
-                } else {
-                    f.write_str(" (saved)")
-                }
-                #[cfg(not(feature = "colored"))]
-                f.write_str(" (saved)")
+            s.clear();
+        }
+        if write!(s, "{}", self).is_err() {
+            s.clear();
+            if write!(s, "{}", default).is_err() {
+                s.clear();
             }
-            (false, false) => Ok(()),
         }
+        s
     }
+}
 
-    #[cfg(feature = "chrono")]
-    pub fn timestamp(self, f: &mut fmt::Formatter, timestamp: &DateTime<Utc>) -> fmt::Result {
-        let rfc2822 = timestamp.with_timezone(&Local).to_rfc2822();
-        #[cfg(feature = "colored")]
-        if self.colored {
-            write!(f, " {}", rfc2822.yellow())
-        } else {
-            write!(f, " {}", rfc2822)
+impl<T: Timestamp> fmt::Display for Formatted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.format, self.utc) {
+            (TimeFormat::Default, None) => Timestamp::fmt(self.timestamp, f),
+            (TimeFormat::Default, Some(utc)) => self.timestamp.fmt_with(f, None, utc),
+            (TimeFormat::Function(format), _) => format(self.timestamp, f),
+            (TimeFormat::Pattern(pattern), utc) => {
+                self.timestamp
+                    .fmt_with(f, Some(pattern), utc.unwrap_or(false))
+            }
+            (TimeFormat::Relative(now), _) => relative(f, now.duration_since(self.timestamp)),
         }
-        #[cfg(not(feature = "colored"))]
-        write!(f, " {}", rfc2822)
     }
 }
 
-#[cfg(feature = "colored")]
-fn color_of_level(i: usize) -> Color {
-    match i % 6 {
-        0 => Color::Cyan,
-        1 => Color::Red,
-        2 => Color::Magenta,
-        3 => Color::Yellow,
-        4 => Color::Green,
-        5 => Color::Blue,
-        _ => unreachable!(),
+/// Writes the duration in the largest whole unit, like `3 minutes ago`.
+fn relative(f: &mut fmt::Formatter, elapsed: Duration) -> fmt::Result {
+    let secs = elapsed.as_secs();
+    let (n, unit) = match secs {
+        0..=59 => (secs, "second"),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    if n == 1 {
+        write!(f, "1 {} ago", unit)
+    } else {
+        write!(f, "{} {}s ago", n, unit)
+    }
+}
+
+/// Writes the command at the position as a JSON object.
+pub(crate) fn json_entry<C: fmt::Display, T: Timestamp>(
+    f: &mut fmt::Formatter,
+    position: usize,
+    entry: &Entry<C, T>,
+) -> fmt::Result {
+    write!(
+        f,
+        "{{\"position\":{},\"node\":{},\"timestamp\":",
+        position, entry.seq
+    )?;
+    entry.timestamp.fmt_json(f)?;
+    f.write_str(",\"command\":")?;
+    json_string(f, &entry.to_string())?;
+    f.write_char('}')
+}
+
+/// Writes the string as a JSON string literal.
+pub(crate) fn json_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
     }
+    f.write_char('"')
 }
@@ -1,15 +1,24 @@
 //! A history of commands.
 
-use crate::{format::Format, At, Command, Entry, Record, Result, Signal};
+use crate::{
+    format::{self, Format, Formatted, Style, TimeFormat},
+    record::Iter,
+    At, Clock, Command, DefaultClock, Entry, Error, NodeId, Outcome, Record, Rollback, Signal,
+    Subscription, Timestamp,
+};
 use alloc::{
-    collections::{BTreeMap, VecDeque},
+    collections::{btree_map, BTreeMap, BTreeSet, VecDeque},
     string::{String, ToString},
     vec,
     vec::Vec,
 };
-#[cfg(feature = "chrono")]
-use chrono::{DateTime, TimeZone};
-use core::fmt::{self, Write};
+use core::{
+    fmt::{self, Write},
+    iter::FusedIterator,
+    mem,
+    ops::RangeBounds,
+    time::Duration,
+};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -25,6 +34,7 @@ use serde::{Deserialize, Serialize};
 /// # impl Command for Add {
 /// #     type Target = String;
 /// #     type Error = &'static str;
+/// #     type Output = ();
 /// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
 /// #         s.push(self.0);
 /// #         Ok(())
@@ -34,7 +44,7 @@ use serde::{Deserialize, Serialize};
 /// #         Ok(())
 /// #     }
 /// # }
-/// # fn main() -> redo::Result<Add> {
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
 /// let mut history = History::default();
 /// history.apply(Add('a'))?;
 /// history.apply(Add('b'))?;
@@ -52,17 +62,62 @@ use serde::{Deserialize, Serialize};
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
-    serde(bound(
-        serialize = "C: Command + Serialize, C::Target: Serialize",
-        deserialize = "C: Command + Deserialize<'de>, C::Target: Deserialize<'de>"
-    ))
+    serde(
+        from = "HistoryData<C, F, K>",
+        bound(
+            serialize = "C: Command + Serialize, C::Target: Serialize, K: Serialize, K::Timestamp: Serialize",
+            deserialize = "C: Command + Deserialize<'de>, C::Target: Deserialize<'de>, K: Default + Deserialize<'de>, K::Timestamp: Deserialize<'de>"
+        )
+    )
 )]
-pub struct History<C: Command, F = fn(Signal)> {
+pub struct History<C: Command, F = fn(Signal), K: Clock = DefaultClock> {
     root: usize,
     next: usize,
     pub(crate) saved: Option<At>,
-    pub(crate) record: Record<C, F>,
-    pub(crate) branches: BTreeMap<usize, Branch<C>>,
+    pub(crate) record: Record<C, F, K>,
+    pub(crate) branches: BTreeMap<usize, Branch<C, K::Timestamp>>,
+    names: BTreeMap<usize, String>,
+    bookmarks: BTreeMap<String, NodeId>,
+}
+
+/// The serialized form of a history, which may come from an earlier version.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(
+    deserialize = "C: Command + Deserialize<'de>, C::Target: Deserialize<'de>, K: Default + Deserialize<'de>, K::Timestamp: Deserialize<'de>"
+))]
+struct HistoryData<C: Command, F, K: Clock> {
+    root: usize,
+    next: usize,
+    saved: Option<At>,
+    record: Record<C, F, K>,
+    branches: BTreeMap<usize, Branch<C, K::Timestamp>>,
+    #[serde(default)]
+    names: BTreeMap<usize, String>,
+    #[serde(default)]
+    bookmarks: BTreeMap<String, NodeId>,
+}
+
+#[cfg(feature = "serde")]
+impl<C: Command, F, K: Clock> From<HistoryData<C, F, K>> for History<C, F, K> {
+    fn from(data: HistoryData<C, F, K>) -> History<C, F, K> {
+        let mut history = History {
+            root: data.root,
+            next: data.next,
+            saved: data.saved,
+            record: data.record,
+            branches: data.branches,
+            names: data.names,
+            bookmarks: data.bookmarks,
+        };
+        // The commands of the root are numbered by the record, so continue with the branches.
+        for branch in history.branches.values_mut() {
+            for entry in &mut branch.entries {
+                entry.renumber(&mut history.record.seq);
+            }
+        }
+        history
+    }
 }
 
 impl<C: Command> History<C> {
@@ -72,7 +127,7 @@ impl<C: Command> History<C> {
     }
 }
 
-impl<C: Command, F> History<C, F> {
+impl<C: Command, F, K: Clock> History<C, F, K> {
     /// Reserves capacity for at least `additional` more commands.
     ///
     /// # Panics
@@ -106,6 +161,38 @@ impl<C: Command, F> History<C, F> {
         self.record.limit()
     }
 
+    /// Returns the heap size budget of the history in bytes, if it has one.
+    pub fn budget(&self) -> Option<usize> {
+        self.record.budget()
+    }
+
+    /// Returns the max age of the commands in the history, if it has one.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.record.max_age()
+    }
+
+    /// Returns the merge window of the history, if it has one.
+    pub fn merge_window(&self) -> Option<Duration> {
+        self.record.merge_window()
+    }
+
+    /// Returns the sum of the [`heap_size`] of the commands in the current branch of the history.
+    ///
+    /// [`heap_size`]: ../trait.Command.html#method.heap_size
+    pub fn heap_size(&self) -> usize {
+        self.record.heap_size()
+    }
+
+    /// Returns a reference to the clock of the history.
+    pub fn clock(&self) -> &K {
+        self.record.clock()
+    }
+
+    /// Returns a mutable reference to the clock of the history.
+    pub fn clock_mut(&mut self) -> &mut K {
+        self.record.clock_mut()
+    }
+
     /// Sets how the signal should be handled when the state changes.
     ///
     /// The previous slot is returned if it exists.
@@ -118,6 +205,19 @@ impl<C: Command, F> History<C, F> {
         self.record.disconnect()
     }
 
+    /// Adds a slot that is called each time the state changes, in addition to the connected slot.
+    ///
+    /// Any number of slots can be subscribed at the same time, and they are called in the order
+    /// they were subscribed. Use a boxed closure as `F` to subscribe closures of different types.
+    pub fn subscribe(&mut self, slot: F) -> Subscription {
+        self.record.subscribe(slot)
+    }
+
+    /// Removes and returns the subscribed slot if it exists.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> Option<F> {
+        self.record.unsubscribe(subscription)
+    }
+
     /// Returns `true` if the target is in a saved state, `false` otherwise.
     pub fn is_saved(&self) -> bool {
         self.record.is_saved()
@@ -143,21 +243,68 @@ impl<C: Command, F> History<C, F> {
         self.record.current()
     }
 
+    /// Returns the identifier of the current state.
+    pub fn node(&self) -> NodeId {
+        self.record.node()
+    }
+
+    /// Returns the position of the state with the identifier,
+    /// or `None` if the state has been removed from the history.
+    pub fn position(&self, node: NodeId) -> Option<At> {
+        match self.record.position(node) {
+            Some(current) => Some(At::new(self.branch(), current)),
+            None => self.branches.iter().find_map(|(&id, branch)| {
+                let i = branch
+                    .entries
+                    .iter()
+                    .position(|entry| entry.seq == node.0)?;
+                Some(At::new(id, branch.parent.current + i + 1))
+            }),
+        }
+    }
+
+    /// Returns the identifier of the state at the position in any of the branches.
+    fn node_at(&self, mut at: At) -> NodeId {
+        while at.branch != self.branch() {
+            let branch = &self.branches[&at.branch];
+            match at.current.checked_sub(branch.parent.current + 1) {
+                Some(i) => return NodeId(branch.entries[i].seq),
+                None => at.branch = branch.parent.branch,
+            }
+        }
+        self.record.node_at(at.current)
+    }
+
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<C, F> {
+    pub fn queue(&mut self) -> Queue<'_, C, F, K> {
         Queue::from(self)
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<C, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, C, F, K> {
         Checkpoint::from(self)
     }
 
     /// Returns a structure for configurable formatting of the history.
-    pub fn display(&self) -> Display<C, F> {
+    pub fn display(&self) -> Display<'_, C, F, K> {
         Display::from(self)
     }
 
+    /// Returns a structure for exporting the history tree in the Graphviz DOT format.
+    pub fn dot(&self) -> Dot<'_, C, F, K> {
+        Dot::from(self)
+    }
+
+    /// Returns a structure for exporting the history as JSON.
+    pub fn json(&self) -> Json<'_, C, F, K> {
+        Json::from(self)
+    }
+
+    /// Returns an iterator over the commands in the current branch, from the oldest to the newest.
+    pub fn iter(&self) -> Iter<'_, C, K::Timestamp> {
+        self.record.iter()
+    }
+
     /// Returns a reference to the `target`.
     pub fn target(&self) -> &C::Target {
         self.record.target()
@@ -175,12 +322,130 @@ impl<C: Command, F> History<C, F> {
         self.record.into_target()
     }
 
-    fn at(&self) -> At {
+    /// Returns the current position in the history.
+    pub fn at(&self) -> At {
         At::new(self.branch(), self.current())
     }
+
+    /// Returns a view of the current branch.
+    ///
+    /// The current branch is the root of the history tree, so it starts at position `0`
+    /// and has no parent.
+    pub fn root(&self) -> BranchView<'_, C, K::Timestamp> {
+        let root = self.branch();
+        BranchView {
+            id: root,
+            parent: None,
+            entries: &self.record.entries,
+            branches: &self.branches,
+            current: self.current(),
+            saved: self.record.saved,
+        }
+    }
+
+    /// Returns a view of the branch, or `None` if the branch does not exist.
+    pub fn get_branch(&self, id: usize) -> Option<BranchView<'_, C, K::Timestamp>> {
+        if id == self.branch() {
+            return Some(self.root());
+        }
+        let branch = self.branches.get(&id)?;
+        Some(BranchView::new(id, branch, &self.branches, self.saved))
+    }
+
+    /// Sets the name of the branch and returns the previous name.
+    ///
+    /// Does nothing and returns `None` if the branch does not exist.
+    /// The name is removed together with the branch, or moves to the branch it is merged into
+    /// if that branch has no name.
+    pub fn set_branch_name(&mut self, branch: usize, name: impl Into<String>) -> Option<String> {
+        self.get_branch(branch)?;
+        self.names.insert(branch, name.into())
+    }
+
+    /// Removes and returns the name of the branch.
+    pub fn remove_branch_name(&mut self, branch: usize) -> Option<String> {
+        self.names.remove(&branch)
+    }
+
+    /// Returns the name of the branch.
+    pub fn branch_name(&self, branch: usize) -> Option<&str> {
+        self.names.get(&branch).map(String::as_str)
+    }
+
+    /// Returns the branch with the name.
+    pub fn find_branch(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .find(|(_, branch_name)| *branch_name == name)
+            .map(|(&branch, _)| branch)
+    }
+
+    /// Bookmarks the state with the identifier under the name and returns the previously bookmarked state.
+    ///
+    /// The bookmark follows the state when branches are switched or commands are removed.
+    pub fn set_bookmark(&mut self, name: impl Into<String>, node: NodeId) -> Option<NodeId> {
+        self.bookmarks.insert(name.into(), node)
+    }
+
+    /// Removes the bookmark and returns the bookmarked state.
+    pub fn remove_bookmark(&mut self, name: &str) -> Option<NodeId> {
+        self.bookmarks.remove(name)
+    }
+
+    /// Returns the position of the bookmarked state,
+    /// or `None` if there is no such bookmark or the state has been removed.
+    pub fn bookmark(&self, name: &str) -> Option<At> {
+        self.position(*self.bookmarks.get(name)?)
+    }
+
+    /// Returns an iterator over the names and positions of the bookmarks, ordered by name.
+    ///
+    /// The bookmarks of the states that have been removed are skipped.
+    pub fn bookmarks(&self) -> impl Iterator<Item = (&str, At)> {
+        self.bookmarks
+            .iter()
+            .filter_map(move |(name, &node)| Some((name.as_str(), self.position(node)?)))
+    }
+
+    /// Returns an iterator over the branches in the history, starting with the current branch.
+    pub fn branches(&self) -> Branches<'_, C, K::Timestamp> {
+        Branches {
+            root: Some(self.root()),
+            all: &self.branches,
+            branches: self.branches.iter(),
+            saved: self.saved,
+        }
+    }
+
+    /// Returns the commands in all the branches together with their positions,
+    /// with the commands in the current branch last.
+    fn entries(&self) -> impl Iterator<Item = (At, &Entry<C, K::Timestamp>)> {
+        let root = self.branch();
+        let branches = self.branches.iter().flat_map(|(&id, branch)| {
+            branch
+                .entries
+                .iter()
+                .enumerate()
+                .map(move |(i, entry)| (At::new(id, branch.parent.current + i + 1), entry))
+        });
+        let record = self
+            .record
+            .entries
+            .iter()
+            .enumerate()
+            .map(move |(i, entry)| (At::new(root, i + 1), entry));
+        branches.chain(record)
+    }
+
+    /// Returns the sequence number of the command at the current position,
+    /// or `None` if it is at the start of the history.
+    fn seq(&self) -> Option<usize> {
+        let current = self.current().checked_sub(1)?;
+        Some(self.record.entries[current].seq)
+    }
 }
 
-impl<C: Command, F: FnMut(Signal)> History<C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> History<C, F, K> {
     /// Marks the target as currently being in a saved or unsaved state.
     pub fn set_saved(&mut self, saved: bool) {
         self.saved = None;
@@ -189,41 +454,76 @@ impl<C: Command, F: FnMut(Signal)> History<C, F> {
 
     /// Removes all commands from the history without undoing them.
     pub fn clear(&mut self) {
+        let old = self.branch();
+        // Only the current state is kept, as the start of the history.
+        let node = self.node();
+        self.bookmarks.retain(|_, bookmark| *bookmark == node);
         self.root = 0;
         self.next = 1;
         self.saved = None;
         self.record.clear();
+        for &id in self.branches.keys() {
+            self.record.slot.emit(Signal::BranchRemoved(id));
+        }
         self.branches.clear();
+        self.names.clear();
+        self.record
+            .slot
+            .emit_if(old != 0, Signal::BranchSwitched { old, new: 0 });
     }
 
     /// Pushes the command to the top of the history and executes its [`apply`] method.
     ///
+    /// Returns the output of the command, and if it was pushed, merged or annulled.
+    ///
     /// # Errors
     /// If an error occur when executing [`apply`] the error is returned.
     ///
     /// [`apply`]: trait.Command.html#tymethod.apply
-    pub fn apply(&mut self, command: C) -> Result<C> {
+    pub fn apply(&mut self, command: C) -> Result<(C::Output, Outcome), Error<C::Error>> {
+        self.__apply(command, true)
+            .map(|(output, outcome, _)| (output, outcome))
+    }
+
+    /// Applies the command, merging it into the command before it only if `merge` is `true`.
+    ///
+    /// Returns the annulled command if it was not moved into a new branch.
+    #[allow(clippy::type_complexity)]
+    fn __apply(
+        &mut self,
+        command: C,
+        merge: bool,
+    ) -> Result<(C::Output, Outcome, Option<Entry<C, K::Timestamp>>), Error<C::Error>> {
         let at = self.at();
         let saved = self.record.saved.filter(|&saved| saved > at.current);
-        let (merged, tail) = self.record.__apply(command)?;
-        // Check if the limit has been reached.
-        if !merged && at.current == self.current() {
-            let root = self.branch();
-            self.rm_child(root, 0);
-            self.branches
-                .values_mut()
-                .filter(|branch| branch.parent.branch == root)
-                .for_each(|branch| branch.parent.current -= 1);
-        }
+        let (output, outcome, evicted, mut tail) = self
+            .record
+            .__apply(command, merge)
+            .map_err(|error| error.on_branch(at.branch))?;
+        self.rm_front(evicted);
+        // The annulled command is only kept if there are commands after it.
+        let annulled = match outcome {
+            Outcome::Annulled if tail.len() == 1 => tail.pop_front(),
+            _ => None,
+        };
         // Handle new branch.
         if !tail.is_empty() {
+            // The annulled command is moved into the new branch.
+            let fork = if let Outcome::Annulled = outcome {
+                at.current - 1 - evicted
+            } else {
+                at.current - evicted
+            };
+            let saved = saved.map(|saved| saved - evicted);
             let new = self.next;
             self.next += 1;
             self.branches
-                .insert(at.branch, Branch::new(new, at.current, tail));
-            self.set_root(new, at.current, saved);
+                .insert(at.branch, Branch::new(new, fork, tail));
+            self.record.slot.emit(Signal::BranchCreated(new));
+            self.set_root(new, fork, saved);
         }
-        Ok(())
+        self.prune_branches();
+        Ok((output, outcome, annulled))
     }
 
     /// Calls the [`undo`] method for the active command
@@ -233,8 +533,9 @@ impl<C: Command, F: FnMut(Signal)> History<C, F> {
     /// If an error occur when executing [`undo`] the error is returned.
     ///
     /// [`undo`]: trait.Command.html#tymethod.undo
-    pub fn undo(&mut self) -> Result<C> {
-        self.record.undo()
+    pub fn undo(&mut self) -> Result<(), Error<C::Error>> {
+        let root = self.branch();
+        self.record.undo().map_err(|error| error.on_branch(root))
     }
 
     /// Calls the [`redo`] method for the active command
@@ -244,52 +545,343 @@ impl<C: Command, F: FnMut(Signal)> History<C, F> {
     /// If an error occur when executing [`redo`] the error is returned.
     ///
     /// [`redo`]: trait.Command.html#method.redo
-    pub fn redo(&mut self) -> Result<C> {
-        self.record.redo()
+    pub fn redo(&mut self) -> Result<(), Error<C::Error>> {
+        let root = self.branch();
+        self.record.redo().map_err(|error| error.on_branch(root))
+    }
+
+    /// Goes to the state that was created right before the current one, regardless of the branch it is in.
+    ///
+    /// The states are ordered by when their commands were applied, so repeatedly calling this method
+    /// visits every state in the history, like `g-` in vim. Does nothing if there is no earlier state.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned,
+    /// together with the number of steps that were completed.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    pub fn earlier(&mut self) -> Result<(), Error<C::Error>> {
+        let seq = match self.seq() {
+            Some(seq) => seq,
+            None => return Ok(()),
+        };
+        let at = self
+            .entries()
+            .filter(|(_, entry)| entry.seq < seq)
+            .max_by_key(|(_, entry)| entry.seq)
+            .map_or(At::new(self.branch(), 0), |(at, _)| at);
+        self.go_to(at.branch, at.current).unwrap()
+    }
+
+    /// Goes to the state that was created right after the current one, regardless of the branch it is in.
+    ///
+    /// This is the reverse of [`earlier`], like `g+` in vim. Does nothing if there is no later state.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned,
+    /// together with the number of steps that were completed.
+    ///
+    /// [`earlier`]: struct.History.html#method.earlier
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    pub fn later(&mut self) -> Result<(), Error<C::Error>> {
+        let seq = self.seq();
+        match self
+            .entries()
+            .filter(|(_, entry)| Some(entry.seq) > seq)
+            .min_by_key(|(_, entry)| entry.seq)
+        {
+            Some((at, _)) => self.go_to(at.branch, at.current).unwrap(),
+            None => Ok(()),
+        }
     }
 
     /// Repeatedly calls [`undo`] or [`redo`] until the command in `branch` at `current` is reached.
     ///
     /// # Errors
-    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned,
+    /// together with the number of steps that were completed.
     ///
     /// [`undo`]: trait.Command.html#tymethod.undo
     /// [`redo`]: trait.Command.html#method.redo
-    pub fn go_to(&mut self, branch: usize, current: usize) -> Option<Result<C>> {
+    pub fn go_to(&mut self, branch: usize, current: usize) -> Option<Result<(), Error<C::Error>>> {
         let root = self.root;
         if root == branch {
-            return self.record.go_to(current);
+            let result = self.record.go_to(current)?;
+            return Some(result.map_err(|error| error.on_branch(root)));
+        }
+        let path = self.mk_path(branch)?;
+        let dest = &self.branches[&branch];
+        if current > dest.parent.current + dest.entries.len() {
+            return None;
         }
+        let mut steps = 0;
         // Walk the path from `root` to `branch`.
-        for (new, branch) in self.mk_path(branch)? {
-            // Walk to `branch.current` either by undoing or redoing.
-            if let Err(err) = self.record.go_to(branch.parent.current).unwrap() {
-                return Some(Err(err));
+        for branch in path {
+            // Walk to the fork point of the branch either by undoing or redoing.
+            let parent = self.branches[&branch].parent;
+            let old = self.current();
+            if let Err(error) = self.record.go_to(parent.current).unwrap() {
+                let steps = steps + error.steps;
+                return Some(Err(error.with_steps(steps).on_branch(self.root)));
             }
-            // Apply the commands in the branch and move older commands into their own branch.
-            for entry in branch.entries {
-                let current = self.current();
-                let saved = self.record.saved.filter(|&saved| saved > current);
-                let entries = match self.record.__apply(entry.command) {
-                    Ok((_, entries)) => entries,
-                    Err(err) => return Some(Err(err)),
+            steps += old.max(parent.current) - old.min(parent.current);
+            // Swap the commands in the branch with the commands after the fork point.
+            self.jump_to(branch);
+        }
+        let root = self.root;
+        let result = self.record.go_to(current).unwrap();
+        Some(result.map_err(|error| {
+            let steps = steps + error.steps;
+            error.with_steps(steps).on_branch(root)
+        }))
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the state with the identifier is reached.
+    ///
+    /// Returns `None` if the state has been removed from the history.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned,
+    /// together with the number of steps that were completed.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    pub fn go_to_node(&mut self, node: NodeId) -> Option<Result<(), Error<C::Error>>> {
+        let at = self.position(node)?;
+        self.go_to(at.branch, at.current)
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the bookmarked state is reached.
+    ///
+    /// Returns `None` if there is no such bookmark or the state has been removed.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned,
+    /// together with the number of steps that were completed.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    pub fn go_to_bookmark(&mut self, name: &str) -> Option<Result<(), Error<C::Error>>> {
+        let at = self.bookmark(name)?;
+        self.go_to(at.branch, at.current)
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the command in `branch` at `current` is reached.
+    ///
+    /// Unlike [`go_to`], the history is restored to the position it started at if an error occurs.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`], the history goes back to
+    /// the starting position and the error is returned in [`Rollback::Restored`].
+    /// If an error also occur when going back, both errors are returned in [`Rollback::Failed`]
+    /// together with the position the history ended up at.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    /// [`go_to`]: struct.History.html#method.go_to
+    /// [`Rollback::Restored`]: ../enum.Rollback.html#variant.Restored
+    /// [`Rollback::Failed`]: ../enum.Rollback.html#variant.Failed
+    pub fn go_to_atomic(
+        &mut self,
+        branch: usize,
+        current: usize,
+    ) -> Option<Result<(), Rollback<Error<C::Error>>>> {
+        let start = self.at();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let mut ids: Vec<usize> = self.branches.keys().copied().collect();
+        ids.push(start.branch);
+        // Temporarily remove slot so they are not called for each step or for the rollback.
+        let slot = mem::take(&mut self.record.slot);
+        let result = self.go_to(branch, current).map(|result| {
+            result.map_err(|error| {
+                // The starting branch is merged into the root if it had no commands after the fork point.
+                let branch = if self.branches.contains_key(&start.branch) {
+                    start.branch
+                } else {
+                    self.branch()
                 };
-                if !entries.is_empty() {
-                    self.branches
-                        .insert(self.root, Branch::new(new, current, entries));
-                    self.set_root(new, current, saved);
+                match self.go_to(branch, start.current).unwrap() {
+                    Ok(()) => Rollback::Restored(error),
+                    Err(rollback) => Rollback::Failed {
+                        error,
+                        rollback,
+                        at: self.at(),
+                    },
                 }
-            }
+            })
+        });
+        // Add slot back.
+        self.record.slot = slot;
+        let root = self.root;
+        self.record.slot.emit_if(
+            start.branch != root,
+            Signal::BranchSwitched {
+                old: start.branch,
+                new: root,
+            },
+        );
+        // A branch is merged into another when it has no commands after the fork point.
+        for id in ids {
+            self.record.slot.emit_if(
+                id != root && !self.branches.contains_key(&id),
+                Signal::BranchRemoved(id),
+            );
+        }
+        self.record
+            .emit_changes(start.current, could_undo, could_redo, was_saved);
+        result
+    }
+
+    /// Go back or forward in the history to the command that was made closest to the timestamp provided.
+    ///
+    /// The command is searched for in all the branches, so this method can jump across branches.
+    /// It goes to the position right after the newest command that was made before the timestamp,
+    /// or to the start of the history if there is no such command.
+    pub fn time_travel(&mut self, to: &K::Timestamp) -> Option<Result<(), Error<C::Error>>> {
+        if self.record.is_empty() && self.branches.is_empty() {
+            return None;
+        }
+        // The current branch is searched last so it is preferred when timestamps are equal.
+        let at = self
+            .entries()
+            .filter(|(_, entry)| entry.timestamp < *to)
+            .max_by_key(|(_, entry)| entry.timestamp)
+            .map_or(At::new(self.branch(), 0), |(at, _)| at);
+        self.go_to(at.branch, at.current)
+    }
+
+    /// Removes the commands that are older than the max age set in the builder.
+    ///
+    /// Only the commands before the current position are removed from the current branch,
+    /// and the other branches are removed when all of their commands are older than the max age.
+    pub fn prune(&mut self) {
+        let evicted = self.record.__prune();
+        self.rm_front(evicted);
+        self.prune_branches();
+    }
+
+    fn prune_branches(&mut self) {
+        let max_age = match self.record.max_age() {
+            Some(max_age) if !self.branches.is_empty() => max_age,
+            _ => return,
+        };
+        let now = self.record.clock_mut().now();
+        self.rm_leaves(|branch| {
+            branch
+                .entries
+                .iter()
+                .all(|entry| now.duration_since(&entry.timestamp) > max_age)
+        });
+    }
+
+    /// Removes the branch and the branches that were forked from it.
+    ///
+    /// Returns `false` if the branch does not exist or is the current branch.
+    pub fn remove_branch(&mut self, branch: usize) -> bool {
+        if !self.branches.contains_key(&branch) {
+            return false;
         }
-        self.record.go_to(current)
+        self.rm_tree(branch);
+        true
     }
 
-    /// Go back or forward in the history to the command that was made closest to the datetime provided.
+    /// Removes all the branches except the current branch.
+    pub fn remove_other_branches(&mut self) {
+        let ids: Vec<_> = self.branches.keys().copied().collect();
+        for id in ids {
+            self.rm_branch(id);
+        }
+    }
+
+    /// Removes the branches where all of the commands were applied before the timestamp.
+    ///
+    /// Branches with children that are not removed are kept, since the children need the commands in them.
+    pub fn remove_branches_before(&mut self, timestamp: &K::Timestamp) {
+        self.rm_leaves(|branch| {
+            branch
+                .entries
+                .iter()
+                .all(|entry| entry.timestamp < *timestamp)
+        });
+    }
+
+    /// Keeps the `n` most recently changed branches in addition to the current branch,
+    /// and removes the rest.
     ///
-    /// This method does not jump across branches.
-    #[cfg(feature = "chrono")]
-    pub fn time_travel(&mut self, to: &DateTime<impl TimeZone>) -> Option<Result<C>> {
-        self.record.time_travel(to)
+    /// When a removed branch has children that are kept, the commands they need
+    /// are moved into the child that was forked last, which takes the place of the removed branch.
+    pub fn truncate_branches(&mut self, n: usize) {
+        if self.branches.len() <= n {
+            return;
+        }
+        // Rank the branches by their most recent command, newest first.
+        let mut ranked: Vec<_> = self
+            .branches
+            .iter()
+            .map(|(&id, branch)| (branch.entries.iter().map(|entry| entry.seq).max(), id))
+            .collect();
+        ranked.sort_unstable_by(|a, b| b.cmp(a));
+        let dead: BTreeSet<_> = ranked[n..].iter().map(|&(_, id)| id).collect();
+        let mut children = self.children();
+        // Visit the branches so that the children are handled before their parents.
+        let mut order = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(children.get(&id).into_iter().flatten());
+        }
+        for id in order.into_iter().rev().filter(|id| dead.contains(id)) {
+            // The dead children have already been removed, so only the kept ones are left.
+            let kept = children.remove(&id).unwrap_or_default();
+            let parent = self.branches[&id].parent;
+            let siblings = children.get_mut(&parent.branch).unwrap();
+            siblings.retain(|&child| child != id);
+            let heir = kept
+                .iter()
+                .copied()
+                .max_by_key(|child| self.branches[child].parent.current);
+            if let Some(heir) = heir {
+                siblings.push(heir);
+                let fork = self.branches[&heir].parent.current;
+                let removed = self.branches.get_mut(&id).unwrap();
+                let mut entries: VecDeque<_> =
+                    removed.entries.drain(..fork - parent.current).collect();
+                let branch = self.branches.get_mut(&heir).unwrap();
+                entries.append(&mut branch.entries);
+                branch.entries = entries;
+                branch.parent = parent;
+                for child in kept.into_iter().filter(|&child| child != heir) {
+                    self.branches.get_mut(&child).unwrap().parent.branch = heir;
+                }
+                // The saved state is kept if its command was moved.
+                if let Some(saved) = self
+                    .saved
+                    .filter(|at| at.branch == id && at.current <= fork)
+                {
+                    self.saved = Some(At::new(heir, saved.current));
+                }
+            }
+            self.rm_branch(id);
+        }
+    }
+
+    /// Removes the branches that were forked from the `evicted` commands at the start of the record.
+    fn rm_front(&mut self, evicted: usize) {
+        for _ in 0..evicted {
+            let root = self.branch();
+            self.rm_child(root, 0);
+            // Positions are counted from the start of the record, so all of them are moved back.
+            self.branches
+                .values_mut()
+                .for_each(|branch| branch.parent.current -= 1);
+            if let Some(saved) = &mut self.saved {
+                saved.current -= 1;
+            }
+        }
     }
 
     pub(crate) fn jump_to(&mut self, root: usize) {
@@ -297,87 +889,133 @@ impl<C: Command, F: FnMut(Signal)> History<C, F> {
         debug_assert_eq!(branch.parent, self.at());
         let current = self.current();
         let saved = self.record.saved.filter(|&saved| saved > current);
-        let tail = self.record.entries.split_off(current);
-        self.record.entries.append(&mut branch.entries);
-        self.branches
-            .insert(self.root, Branch::new(root, current, tail));
+        self.record.saved = self.record.saved.filter(|&saved| saved <= current);
+        let tail = self.record.split_off(current);
+        self.record.append(&mut branch.entries);
+        let old = self.root;
+        let merged = tail.is_empty();
+        if merged {
+            // The old root is merged into the new root, which takes over its name if it has none.
+            if let Some(name) = self.names.remove(&old) {
+                self.names.entry(root).or_insert(name);
+            }
+        } else {
+            self.branches.insert(old, Branch::new(root, current, tail));
+        }
         self.set_root(root, current, saved);
+        self.record.slot.emit_if(merged, Signal::BranchRemoved(old));
     }
 
     fn set_root(&mut self, root: usize, current: usize, saved: Option<usize>) {
         let old = self.branch();
+        let was_saved = self.is_saved();
         self.root = root;
         debug_assert_ne!(old, root);
+        self.record
+            .slot
+            .emit(Signal::BranchSwitched { old, new: root });
         // Handle the child branches.
         self.branches
             .values_mut()
             .filter(|branch| branch.parent.branch == old && branch.parent.current <= current)
             .for_each(|branch| branch.parent.branch = root);
-        match (self.record.saved, saved, self.saved) {
-            (Some(_), None, None) | (None, None, Some(_)) => self.swap_saved(root, old, current),
-            (None, Some(_), None) => {
-                self.record.saved = saved;
-                self.swap_saved(old, root, current);
-            }
-            (None, None, None) => (),
-            _ => unreachable!(),
-        }
-    }
-
-    fn swap_saved(&mut self, old: usize, new: usize, current: usize) {
-        debug_assert_ne!(old, new);
-        if let Some(At { current: saved, .. }) = self
-            .saved
-            .filter(|at| at.branch == new && at.current <= current)
-        {
-            self.saved = None;
-            self.record.saved = Some(saved);
-            self.record.slot.emit(Signal::Saved(true));
-        } else if let Some(saved) = self.record.saved {
+        // Move the saved state along with the commands it belongs to.
+        if let Some(saved) = saved {
+            debug_assert_eq!(self.saved, None);
             self.saved = Some(At::new(old, saved));
-            self.record.saved = None;
-            self.record.slot.emit(Signal::Saved(false));
+        } else if let Some(at) = self.saved.filter(|at| at.branch == root) {
+            self.saved = None;
+            self.record.saved = Some(at.current);
         }
+        let is_saved = self.is_saved();
+        self.record
+            .slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
     }
 
     fn rm_child(&mut self, branch: usize, current: usize) {
         // We need to check if any of the branches had the removed node as root.
-        let mut dead: Vec<_> = self
+        let dead: Vec<_> = self
             .branches
             .iter()
             .filter(|&(_, child)| child.parent == At::new(branch, current))
             .map(|(&id, _)| id)
             .collect();
+        for id in dead {
+            self.rm_tree(id);
+        }
+    }
+
+    /// Removes the branch and all of its descendants.
+    fn rm_tree(&mut self, id: usize) {
+        let children = self.children();
+        let mut dead = vec![id];
         while let Some(parent) = dead.pop() {
             // Remove the dead branch.
-            self.branches.remove(&parent).unwrap();
-            self.saved = self.saved.filter(|saved| saved.branch != parent);
+            self.rm_branch(parent);
             // Add the children of the dead branch so they are removed too.
-            dead.extend(
-                self.branches
-                    .iter()
-                    .filter(|&(_, child)| child.parent.branch == parent)
-                    .map(|(&id, _)| id),
-            )
+            dead.extend(children.get(&parent).into_iter().flatten());
+        }
+    }
+
+    /// Repeatedly removes the branches without children that match the predicate.
+    fn rm_leaves(&mut self, mut f: impl FnMut(&Branch<C, K::Timestamp>) -> bool) {
+        let mut children = self.children();
+        let mut leaves: Vec<_> = self
+            .branches
+            .keys()
+            .rev()
+            .filter(|id| !children.contains_key(id))
+            .copied()
+            .collect();
+        while let Some(id) = leaves.pop() {
+            if !f(&self.branches[&id]) {
+                continue;
+            }
+            let parent = self.branches[&id].parent.branch;
+            self.rm_branch(id);
+            // The parent becomes a leaf when its last child is removed.
+            let siblings = children.get_mut(&parent).unwrap();
+            siblings.retain(|&child| child != id);
+            if siblings.is_empty() && parent != self.root {
+                leaves.push(parent);
+            }
+        }
+    }
+
+    /// Returns the identifiers of the child branches of each branch that has children.
+    fn children(&self) -> BTreeMap<usize, Vec<usize>> {
+        let mut children = BTreeMap::new();
+        for (&id, branch) in &self.branches {
+            children
+                .entry(branch.parent.branch)
+                .or_insert_with(Vec::new)
+                .push(id);
         }
+        children
     }
 
-    fn mk_path(&mut self, mut to: usize) -> Option<impl Iterator<Item = (usize, Branch<C>)>> {
+    /// Removes the branch together with its name and saved state.
+    fn rm_branch(&mut self, id: usize) {
+        self.branches.remove(&id).unwrap();
+        self.names.remove(&id);
+        self.saved = self.saved.filter(|saved| saved.branch != id);
+        self.record.slot.emit(Signal::BranchRemoved(id));
+    }
+
+    fn mk_path(&self, mut to: usize) -> Option<Vec<usize>> {
         debug_assert_ne!(self.branch(), to);
-        let mut dest = self.branches.remove(&to)?;
-        let mut i = dest.parent.branch;
-        let mut path = vec![(to, dest)];
-        while i != self.branch() {
-            dest = self.branches.remove(&i).unwrap();
-            to = i;
-            i = dest.parent.branch;
-            path.push((to, dest));
+        let mut path = Vec::new();
+        while to != self.branch() {
+            path.push(to);
+            to = self.branches.get(&to)?.parent.branch;
         }
-        Some(path.into_iter().rev())
+        path.reverse();
+        Some(path)
     }
 }
 
-impl<C: Command + ToString, F> History<C, F> {
+impl<C: Command + ToString, F, K: Clock> History<C, F, K> {
     /// Returns the string of the command which will be undone in the next call to [`undo`].
     ///
     /// [`undo`]: struct.History.html#method.undo
@@ -402,22 +1040,26 @@ where
     }
 }
 
-impl<C: Command, F> From<Record<C, F>> for History<C, F> {
-    fn from(record: Record<C, F>) -> Self {
+impl<C: Command, F, K: Clock> From<Record<C, F, K>> for History<C, F, K> {
+    fn from(record: Record<C, F, K>) -> Self {
         History {
             root: 0,
             next: 1,
             saved: None,
             record,
             branches: BTreeMap::default(),
+            names: BTreeMap::default(),
+            bookmarks: BTreeMap::default(),
         }
     }
 }
 
-impl<C: Command, F> fmt::Debug for History<C, F>
+impl<C: Command, F, K: Clock> fmt::Debug for History<C, F, K>
 where
     C: fmt::Debug,
     C::Target: fmt::Debug,
+    K: fmt::Debug,
+    K::Timestamp: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("History")
@@ -426,20 +1068,26 @@ where
             .field("saved", &self.saved)
             .field("record", &self.record)
             .field("branches", &self.branches)
+            .field("names", &self.names)
+            .field("bookmarks", &self.bookmarks)
             .finish()
     }
 }
 
 /// A branch in the history.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(bound(deserialize = "C: Deserialize<'de>, T: Timestamp + Deserialize<'de>"))
+)]
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
-pub(crate) struct Branch<C> {
+pub(crate) struct Branch<C, T> {
     pub(crate) parent: At,
-    pub(crate) entries: VecDeque<Entry<C>>,
+    pub(crate) entries: VecDeque<Entry<C, T>>,
 }
 
-impl<C> Branch<C> {
-    fn new(branch: usize, current: usize, entries: VecDeque<Entry<C>>) -> Branch<C> {
+impl<C, T> Branch<C, T> {
+    fn new(branch: usize, current: usize, entries: VecDeque<Entry<C, T>>) -> Branch<C, T> {
         Branch {
             parent: At::new(branch, current),
             entries,
@@ -447,11 +1095,130 @@ impl<C> Branch<C> {
     }
 }
 
+/// A read-only view of a branch in the history.
+///
+/// This struct is created by the [`root`], [`get_branch`] and [`branches`] methods.
+///
+/// [`root`]: struct.History.html#method.root
+/// [`get_branch`]: struct.History.html#method.get_branch
+/// [`branches`]: struct.History.html#method.branches
+#[derive(Debug)]
+pub struct BranchView<'a, C, T> {
+    id: usize,
+    parent: Option<At>,
+    entries: &'a VecDeque<Entry<C, T>>,
+    branches: &'a BTreeMap<usize, Branch<C, T>>,
+    current: usize,
+    saved: Option<usize>,
+}
+
+impl<'a, C, T> BranchView<'a, C, T> {
+    fn new(
+        id: usize,
+        branch: &'a Branch<C, T>,
+        branches: &'a BTreeMap<usize, Branch<C, T>>,
+        saved: Option<At>,
+    ) -> BranchView<'a, C, T> {
+        BranchView {
+            id,
+            parent: Some(branch.parent),
+            entries: &branch.entries,
+            branches,
+            // None of the commands in the other branches are applied.
+            current: 0,
+            saved: saved.filter(|at| at.branch == id).map(|at| at.current),
+        }
+    }
+
+    /// Returns the identifier of the branch.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the position the branch was forked from,
+    /// or `None` if it is the current branch.
+    pub fn parent(&self) -> Option<At> {
+        self.parent
+    }
+
+    /// Returns the number of commands in the branch.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the branch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the commands in the branch, from the oldest to the newest.
+    ///
+    /// The index of a command is its position in the branch, counted from the start of the history.
+    pub fn iter(&self) -> Iter<'a, C, T> {
+        let offset = self.parent.map_or(0, |parent| parent.current);
+        Iter::new(self.entries, offset, self.current, self.saved)
+    }
+
+    /// Returns an iterator over the identifiers of the branches that were forked from this branch.
+    pub fn children(&self) -> impl Iterator<Item = usize> + 'a {
+        let id = self.id;
+        self.branches
+            .iter()
+            .filter(move |(_, branch)| branch.parent.branch == id)
+            .map(|(&id, _)| id)
+    }
+}
+
+impl<C, T> Clone for BranchView<'_, C, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C, T> Copy for BranchView<'_, C, T> {}
+
+/// Iterator over the branches in a history.
+///
+/// This struct is created by the [`branches`](struct.History.html#method.branches) method.
+#[derive(Debug)]
+pub struct Branches<'a, C, T> {
+    root: Option<BranchView<'a, C, T>>,
+    all: &'a BTreeMap<usize, Branch<C, T>>,
+    branches: btree_map::Iter<'a, usize, Branch<C, T>>,
+    saved: Option<At>,
+}
+
+impl<'a, C, T> Iterator for Branches<'a, C, T> {
+    type Item = BranchView<'a, C, T>;
+
+    fn next(&mut self) -> Option<BranchView<'a, C, T>> {
+        if let Some(root) = self.root.take() {
+            return Some(root);
+        }
+        let (&id, branch) = self.branches.next()?;
+        Some(BranchView::new(id, branch, self.all, self.saved))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.branches.size_hint();
+        let root = self.root.is_some() as usize;
+        (lower + root, upper.map(|upper| upper + root))
+    }
+}
+
+impl<C, T> ExactSizeIterator for Branches<'_, C, T> {}
+
+impl<C, T> FusedIterator for Branches<'_, C, T> {}
+
 /// Builder for a History.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(bound(deserialize = "K: Default + Deserialize<'de>"))
+)]
 #[derive(Debug)]
-pub struct Builder {
-    inner: crate::record::Builder,
+pub struct Builder<K = DefaultClock> {
+    inner: crate::record::Builder<K>,
 }
 
 impl Builder {
@@ -461,9 +1228,11 @@ impl Builder {
             inner: crate::record::Builder::new(),
         }
     }
+}
 
+impl<K: Clock> Builder<K> {
     /// Sets the capacity for the history.
-    pub fn capacity(&mut self, capacity: usize) -> &mut Builder {
+    pub fn capacity(&mut self, capacity: usize) -> &mut Builder<K> {
         self.inner.capacity(capacity);
         self
     }
@@ -472,40 +1241,101 @@ impl Builder {
     ///
     /// # Panics
     /// Panics if `limit` is `0`.
-    pub fn limit(&mut self, limit: usize) -> &mut Builder {
+    pub fn limit(&mut self, limit: usize) -> &mut Builder<K> {
         self.inner.limit(limit);
         self
     }
 
-    /// Sets if the target is initially in a saved state.
+    /// Sets the heap size `budget` of the history in bytes.
+    ///
+    /// When the sum of the [`heap_size`] of the commands in the current branch exceeds the budget,
+    /// the oldest commands are removed until it fits, but the newest command is always kept.
+    /// Branches that were created from the removed commands are removed as well.
+    /// By default the history has no budget.
+    ///
+    /// [`heap_size`]: ../trait.Command.html#method.heap_size
+    pub fn budget(&mut self, budget: usize) -> &mut Builder<K> {
+        self.inner.budget(budget);
+        self
+    }
+
+    /// Sets the max age of the commands in the history.
+    ///
+    /// The commands that are older than the max age are removed when a new command is applied,
+    /// or when [`prune`] is called. By default the commands are kept regardless of their age.
+    ///
+    /// [`prune`]: struct.History.html#method.prune
+    pub fn max_age(&mut self, max_age: Duration) -> &mut Builder<K> {
+        self.inner.max_age(max_age);
+        self
+    }
+
+    /// Sets the merge window of the history.
+    ///
+    /// A command is only merged with the last command if it is applied within the window
+    /// after the last command was applied or merged into.
+    /// By default the commands are merged regardless of how long ago the last command was applied.
+    pub fn merge_window(&mut self, window: Duration) -> &mut Builder<K> {
+        self.inner.merge_window(window);
+        self
+    }
+
+    /// Sets the clock that time stamps the commands in the history.
+    ///
+    /// By default the [`DefaultClock`] is used.
+    ///
+    /// [`DefaultClock`]: ../type.DefaultClock.html
+    pub fn clock<L: Clock>(&self, clock: L) -> Builder<L> {
+        Builder {
+            inner: self.inner.clock(clock),
+        }
+    }
+
+    /// Sets if the target is initially in a saved state.
     /// By default the target is in a saved state.
-    pub fn saved(&mut self, saved: bool) -> &mut Builder {
+    pub fn saved(&mut self, saved: bool) -> &mut Builder<K> {
         self.inner.saved(saved);
         self
     }
 
+    /// Sets if the slot receives the signals that report changes, like `Applied` and `Merged`.
+    /// If off, only the `Undo`, `Redo` and `Saved` signals are emitted.
+    /// By default all signals are emitted.
+    pub fn events(&mut self, on: bool) -> &mut Builder<K> {
+        self.inner.events(on);
+        self
+    }
+
     /// Builds the history.
-    pub fn build<C: Command>(&self, target: C::Target) -> History<C> {
+    pub fn build<C: Command>(&self, target: C::Target) -> History<C, fn(Signal), K>
+    where
+        K: Clone,
+    {
         History::from(self.inner.build(target))
     }
 
     /// Builds the history with the slot.
-    pub fn build_with<C: Command, F>(&self, target: C::Target, slot: F) -> History<C, F> {
+    pub fn build_with<C: Command, F>(&self, target: C::Target, slot: F) -> History<C, F, K>
+    where
+        K: Clone,
+    {
         History::from(self.inner.build_with(target, slot))
     }
 
     /// Creates the history with a default `target`.
-    pub fn default<C: Command>(&self) -> History<C>
+    pub fn default<C: Command>(&self) -> History<C, fn(Signal), K>
     where
         C::Target: Default,
+        K: Clone,
     {
         self.build(Default::default())
     }
 
     /// Creates the history with a default `target` and with the slot.
-    pub fn default_with<C: Command, F>(&self, slot: F) -> History<C, F>
+    pub fn default_with<C: Command, F>(&self, slot: F) -> History<C, F, K>
     where
         C::Target: Default,
+        K: Clone,
     {
         self.build_with(Default::default(), slot)
     }
@@ -533,6 +1363,7 @@ enum QueueCommand<C> {
 /// # impl Command for Add {
 /// #     type Target = String;
 /// #     type Error = &'static str;
+/// #     type Output = ();
 /// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
 /// #         s.push(self.0);
 /// #         Ok(())
@@ -542,7 +1373,7 @@ enum QueueCommand<C> {
 /// #         Ok(())
 /// #     }
 /// # }
-/// # fn main() -> redo::Result<Add> {
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
 /// let mut record = Record::default();
 /// let mut queue = record.queue();
 /// queue.apply(Add('a'));
@@ -554,12 +1385,12 @@ enum QueueCommand<C> {
 /// # Ok(())
 /// # }
 /// ```
-pub struct Queue<'a, C: Command, F> {
-    history: &'a mut History<C, F>,
+pub struct Queue<'a, C: Command, F, K: Clock = DefaultClock> {
+    history: &'a mut History<C, F, K>,
     commands: Vec<QueueCommand<C>>,
 }
 
-impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> Queue<'_, C, F, K> {
     /// Queues an `apply` action.
     pub fn apply(&mut self, command: C) {
         self.commands.push(QueueCommand::Apply(command));
@@ -575,17 +1406,19 @@ impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
         self.commands.push(QueueCommand::Redo);
     }
 
-    /// Applies the queued commands.
+    /// Applies the queued commands. The outputs of the commands are discarded.
     ///
     /// # Errors
-    /// If an error occurs, it stops applying the commands and returns the error.
-    pub fn commit(self) -> Result<C> {
-        for command in self.commands {
+    /// If an error occurs, it stops applying the commands and returns the error,
+    /// together with the number of commands that were applied.
+    pub fn commit(self) -> Result<(), Error<C::Error>> {
+        for (steps, command) in self.commands.into_iter().enumerate() {
             match command {
-                QueueCommand::Apply(command) => self.history.apply(command)?,
-                QueueCommand::Undo => self.history.undo()?,
-                QueueCommand::Redo => self.history.redo()?,
+                QueueCommand::Apply(command) => self.history.apply(command).map(|_| ()),
+                QueueCommand::Undo => self.history.undo(),
+                QueueCommand::Redo => self.history.redo(),
             }
+            .map_err(|error| error.with_steps(steps))?;
         }
         Ok(())
     }
@@ -594,12 +1427,12 @@ impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
     pub fn cancel(self) {}
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<C, F> {
+    pub fn queue(&mut self) -> Queue<'_, C, F, K> {
         self.history.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<C, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, C, F, K> {
         self.history.checkpoint()
     }
 
@@ -609,8 +1442,8 @@ impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
     }
 }
 
-impl<'a, C: Command, F> From<&'a mut History<C, F>> for Queue<'a, C, F> {
-    fn from(history: &'a mut History<C, F>) -> Self {
+impl<'a, C: Command, F, K: Clock> From<&'a mut History<C, F, K>> for Queue<'a, C, F, K> {
+    fn from(history: &'a mut History<C, F, K>) -> Self {
         Queue {
             history,
             commands: Vec::new(),
@@ -619,29 +1452,35 @@ impl<'a, C: Command, F> From<&'a mut History<C, F>> for Queue<'a, C, F> {
 }
 
 #[derive(Debug)]
-enum CheckpointCommand {
-    Apply(usize),
+enum CheckpointCommand<C, T> {
+    Apply(usize, Outcome, Option<Entry<C, T>>),
     Undo,
     Redo,
 }
 
 /// Wraps a history and gives it checkpoint functionality.
-pub struct Checkpoint<'a, C: Command, F> {
-    history: &'a mut History<C, F>,
-    commands: Vec<CheckpointCommand>,
+pub struct Checkpoint<'a, C: Command, F, K: Clock = DefaultClock> {
+    history: &'a mut History<C, F, K>,
+    commands: Vec<CheckpointCommand<C, K::Timestamp>>,
+    seq: usize,
 }
 
-impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> Checkpoint<'_, C, F, K> {
     /// Calls the `apply` method.
-    pub fn apply(&mut self, command: C) -> Result<C> {
+    ///
+    /// The command is not merged into a command that was applied before the checkpoint,
+    /// since that command could not be restored if the checkpoint is canceled.
+    pub fn apply(&mut self, command: C) -> Result<(C::Output, Outcome), Error<C::Error>> {
         let branch = self.history.branch();
-        self.history.apply(command)?;
-        self.commands.push(CheckpointCommand::Apply(branch));
-        Ok(())
+        let merge = self.history.record.applied_since(self.seq);
+        let (output, outcome, annulled) = self.history.__apply(command, merge)?;
+        self.commands
+            .push(CheckpointCommand::Apply(branch, outcome, annulled));
+        Ok((output, outcome))
     }
 
     /// Calls the `undo` method.
-    pub fn undo(&mut self) -> Result<C> {
+    pub fn undo(&mut self) -> Result<(), Error<C::Error>> {
         if self.history.can_undo() {
             self.history.undo()?;
             self.commands.push(CheckpointCommand::Undo);
@@ -650,7 +1489,7 @@ impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
     }
 
     /// Calls the `redo` method.
-    pub fn redo(&mut self) -> Result<C> {
+    pub fn redo(&mut self) -> Result<(), Error<C::Error>> {
         if self.history.can_redo() {
             self.history.redo()?;
             self.commands.push(CheckpointCommand::Redo);
@@ -665,33 +1504,54 @@ impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
     ///
     /// # Errors
     /// If an error occur when canceling the changes, the error is returned
+    /// together with the number of commands that were canceled,
     /// and the remaining commands are not canceled.
-    pub fn cancel(self) -> Result<C> {
-        for command in self.commands.into_iter().rev() {
+    pub fn cancel(self) -> Result<(), Error<C::Error>> {
+        for (steps, command) in self.commands.into_iter().rev().enumerate() {
+            let with_steps = |error: Error<C::Error>| error.with_steps(steps);
             match command {
-                CheckpointCommand::Apply(branch) => {
+                CheckpointCommand::Apply(branch, outcome, annulled) => {
                     let root = self.history.branch();
-                    self.history.jump_to(branch);
-                    if root == branch {
-                        self.history.record.entries.pop_back();
-                    } else {
-                        self.history.branches.remove(&root).unwrap();
+                    match outcome {
+                        Outcome::Pushed(_) | Outcome::Evicted(_) => {
+                            self.history.undo().map_err(with_steps)?;
+                            if root == branch {
+                                self.history.record.pop_back();
+                            } else {
+                                self.history.jump_to(branch);
+                                self.history.rm_branch(root);
+                            }
+                        }
+                        // The command it was merged into is removed when its own apply is canceled.
+                        Outcome::Merged(_) => {
+                            if root != branch {
+                                self.history.jump_to(branch);
+                            }
+                        }
+                        // Put back the annulled command, which is first in the branch if one was created.
+                        Outcome::Annulled => {
+                            match annulled {
+                                Some(entry) => self.history.record.push_back(entry),
+                                None => self.history.jump_to(branch),
+                            }
+                            self.history.redo().map_err(with_steps)?;
+                        }
                     }
                 }
-                CheckpointCommand::Undo => self.history.redo()?,
-                CheckpointCommand::Redo => self.history.undo()?,
+                CheckpointCommand::Undo => self.history.redo().map_err(with_steps)?,
+                CheckpointCommand::Redo => self.history.undo().map_err(with_steps)?,
             }
         }
         Ok(())
     }
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<C, F> {
+    pub fn queue(&mut self) -> Queue<'_, C, F, K> {
         self.history.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<C, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, C, F, K> {
         self.history.checkpoint()
     }
 
@@ -701,9 +1561,10 @@ impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
     }
 }
 
-impl<'a, C: Command, F> From<&'a mut History<C, F>> for Checkpoint<'a, C, F> {
-    fn from(history: &'a mut History<C, F>) -> Self {
+impl<'a, C: Command, F, K: Clock> From<&'a mut History<C, F, K>> for Checkpoint<'a, C, F, K> {
+    fn from(history: &'a mut History<C, F, K>) -> Self {
         Checkpoint {
+            seq: history.record.seq,
             history,
             commands: Vec::new(),
         }
@@ -712,12 +1573,14 @@ impl<'a, C: Command, F> From<&'a mut History<C, F>> for Checkpoint<'a, C, F> {
 
 /// Configurable display formatting for history.
 #[derive(Copy, Clone)]
-pub struct Display<'a, C: Command, F> {
-    history: &'a History<C, F>,
-    format: Format,
+pub struct Display<'a, C: Command, F, K: Clock = DefaultClock> {
+    history: &'a History<C, F, K>,
+    format: Format<'a>,
+    time: TimeFormat<'a, K::Timestamp>,
+    utc: Option<bool>,
 }
 
-impl<C: Command, F> Display<'_, C, F> {
+impl<'a, C: Command, F, K: Clock> Display<'a, C, F, K> {
     /// Show colored output (on by default).
     ///
     /// Requires the `colored` feature to be enabled.
@@ -739,6 +1602,12 @@ impl<C: Command, F> Display<'_, C, F> {
         self
     }
 
+    /// Show the names of the branches and the bookmarks (on by default).
+    pub fn names(&mut self, on: bool) -> &mut Self {
+        self.format.names = on;
+        self
+    }
+
     /// Show the position of the command (on by default).
     pub fn position(&mut self, on: bool) -> &mut Self {
         self.format.position = on;
@@ -750,25 +1619,100 @@ impl<C: Command, F> Display<'_, C, F> {
         self.format.saved = on;
         self
     }
+
+    /// Show only the commands at most `n` positions away from the current position.
+    ///
+    /// The positions are compared in all the branches. The commands that are not shown
+    /// are summarized together with the branches that are forked from them, like `… 420 more …`.
+    pub fn window(&mut self, n: usize) -> &mut Self {
+        self.format.set_window(self.history.current(), n);
+        self
+    }
+
+    /// Show only the commands at the positions in the range.
+    ///
+    /// The positions are compared in all the branches. The commands that are not shown
+    /// are summarized together with the branches that are forked from them, like `… 420 more …`.
+    pub fn range(&mut self, range: impl RangeBounds<usize>) -> &mut Self {
+        self.format.set_range(range);
+        self
+    }
+
+    /// Sets the symbols, labels and colors of the output.
+    pub fn style(&mut self, style: Style<'a>) -> &mut Self {
+        self.format.style = style;
+        self
+    }
+
+    /// Formats the timestamps by using the function.
+    ///
+    /// The timestamps are formatted by using [`Timestamp::fmt`] by default.
+    ///
+    /// [`Timestamp::fmt`]: ../trait.Timestamp.html#method.fmt
+    pub fn timestamp(
+        &mut self,
+        format: fn(&K::Timestamp, &mut fmt::Formatter) -> fmt::Result,
+    ) -> &mut Self {
+        self.time = TimeFormat::Function(format);
+        self
+    }
+
+    /// Formats the timestamps with a strftime-style pattern, like `%H:%M:%S`.
+    ///
+    /// The pattern is ignored by timestamps without a calendar date and time,
+    /// see [`Timestamp::fmt_with`]. The timestamps are formatted as by default
+    /// if the pattern is not valid.
+    ///
+    /// [`Timestamp::fmt_with`]: ../trait.Timestamp.html#method.fmt_with
+    pub fn pattern(&mut self, pattern: &'a str) -> &mut Self {
+        self.time = TimeFormat::Pattern(pattern);
+        self
+    }
+
+    /// Formats the timestamps as the time elapsed until `now`, like `3 minutes ago`.
+    ///
+    /// Timestamps later than `now` are shown as `0 seconds ago`, and timestamps
+    /// that are formatted as nothing by [`Timestamp::fmt`], like `()`, are not shown.
+    ///
+    /// [`Timestamp::fmt`]: ../trait.Timestamp.html#method.fmt
+    pub fn relative(&mut self, now: K::Timestamp) -> &mut Self {
+        self.time = TimeFormat::Relative(now);
+        self
+    }
+
+    /// Show the timestamps in UTC if `on` is `true`, or in the local time zone if it is `false`.
+    ///
+    /// By default the timestamps are shown as formatted by [`Timestamp::fmt`]
+    /// and the patterns are formatted in the local time zone.
+    ///
+    /// [`Timestamp::fmt`]: ../trait.Timestamp.html#method.fmt
+    pub fn utc(&mut self, on: bool) -> &mut Self {
+        self.utc = Some(on);
+        self
+    }
 }
 
-impl<C: Command + fmt::Display, F> Display<'_, C, F> {
+impl<C: Command + fmt::Display, F, K: Clock> Display<'_, C, F, K> {
     fn fmt_list(
         &self,
         f: &mut fmt::Formatter,
         at: At,
-        entry: Option<&Entry<C>>,
+        entry: Option<&Entry<C, K::Timestamp>>,
         level: usize,
     ) -> fmt::Result {
         self.format.mark(f, level)?;
         self.format.position(f, at, true)?;
 
-        #[cfg(feature = "chrono")]
-        {
-            if let Some(entry) = entry {
-                if self.format.detailed {
-                    self.format.timestamp(f, &entry.timestamp)?;
-                }
+        if let Some(entry) = entry {
+            if self.format.detailed {
+                self.format.timestamp(
+                    f,
+                    &Formatted {
+                        timestamp: &entry.timestamp,
+                        format: self.time,
+                        utc: self.utc,
+                    },
+                )?;
             }
         }
 
@@ -782,6 +1726,9 @@ impl<C: Command + fmt::Display, F> Display<'_, C, F> {
                 .map(|saved| At::new(self.history.branch(), saved))
                 .or(self.history.saved),
         )?;
+        if self.format.names {
+            self.fmt_names(f, at, entry)?;
+        }
         if let Some(entry) = entry {
             if self.format.detailed {
                 writeln!(f)?;
@@ -795,11 +1742,41 @@ impl<C: Command + fmt::Display, F> Display<'_, C, F> {
         Ok(())
     }
 
+    fn fmt_names(
+        &self,
+        f: &mut fmt::Formatter,
+        at: At,
+        entry: Option<&Entry<C, K::Timestamp>>,
+    ) -> fmt::Result {
+        // The name of a branch is shown at its last command.
+        let end = if at.branch == self.history.branch() {
+            self.history.len()
+        } else {
+            let branch = &self.history.branches[&at.branch];
+            branch.parent.current + branch.entries.len()
+        };
+        if at.current == end {
+            if let Some(name) = self.history.branch_name(at.branch) {
+                self.format.name(f, name)?;
+            }
+        }
+        let node = entry.map_or_else(|| self.history.record.node_at(0), |entry| NodeId(entry.seq));
+        for (name, _) in self
+            .history
+            .bookmarks
+            .iter()
+            .filter(|&(_, &bookmark)| bookmark == node)
+        {
+            self.format.name(f, name)?;
+        }
+        Ok(())
+    }
+
     fn fmt_graph(
         &self,
         f: &mut fmt::Formatter,
         at: At,
-        entry: Option<&Entry<C>>,
+        entry: Option<&Entry<C, K::Timestamp>>,
         level: usize,
     ) -> fmt::Result {
         for (&i, branch) in self
@@ -808,10 +1785,18 @@ impl<C: Command + fmt::Display, F> Display<'_, C, F> {
             .iter()
             .filter(|(_, branch)| branch.parent == at)
         {
+            let mut elided = 0;
             for (j, entry) in branch.entries.iter().enumerate().rev() {
                 let at = At::new(i, j + branch.parent.current + 1);
-                self.fmt_graph(f, at, Some(entry), level + 1)?;
+                if self.format.is_visible(at) {
+                    self.fmt_elided(f, elided, level + 1)?;
+                    elided = 0;
+                    self.fmt_graph(f, at, Some(entry), level + 1)?;
+                } else {
+                    elided += 1 + self.count(at);
+                }
             }
+            self.fmt_elided(f, elided, level + 1)?;
             for j in 0..level {
                 self.format.edge(f, j)?;
                 f.write_char(' ')?;
@@ -825,38 +1810,363 @@ impl<C: Command + fmt::Display, F> Display<'_, C, F> {
         }
         self.fmt_list(f, at, entry, level)
     }
+
+    /// Writes the summary of the `n` commands that are not shown, if there are any.
+    fn fmt_elided(&self, f: &mut fmt::Formatter, n: usize, level: usize) -> fmt::Result {
+        if n == 0 {
+            return Ok(());
+        }
+        for i in 0..level {
+            self.format.edge(f, i)?;
+            f.write_char(' ')?;
+        }
+        self.format.elided(f, n)?;
+        writeln!(f)
+    }
+
+    /// Returns the number of commands in the branches that are forked from the position.
+    fn count(&self, at: At) -> usize {
+        self.history
+            .branches
+            .iter()
+            .filter(|(_, branch)| branch.parent == at)
+            .map(|(&i, branch)| {
+                (1..=branch.entries.len())
+                    .map(|j| 1 + self.count(At::new(i, branch.parent.current + j)))
+                    .sum::<usize>()
+            })
+            .sum()
+    }
 }
 
-impl<'a, C: Command, F> From<&'a History<C, F>> for Display<'a, C, F> {
-    fn from(history: &'a History<C, F>) -> Self {
+impl<'a, C: Command, F, K: Clock> From<&'a History<C, F, K>> for Display<'a, C, F, K> {
+    fn from(history: &'a History<C, F, K>) -> Self {
         Display {
             history,
             format: Format::default(),
+            time: TimeFormat::Default,
+            utc: None,
         }
     }
 }
 
-impl<C: Command + fmt::Display, F> fmt::Display for Display<'_, C, F> {
+impl<C: Command + fmt::Display, F, K: Clock> fmt::Display for Display<'_, C, F, K> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let branch = self.history.branch();
-        for (i, entry) in self.history.record.entries.iter().enumerate().rev() {
-            let at = At::new(branch, i + 1);
-            self.fmt_graph(f, at, Some(entry), 0)?;
+        let mut elided = 0;
+        for i in (0..=self.history.len()).rev() {
+            let at = At::new(branch, i);
+            if !self.format.is_visible(at) {
+                // The start of the history is not a command, so only its branches are counted.
+                elided += usize::from(i > 0) + self.count(at);
+                continue;
+            }
+            self.fmt_elided(f, elided, 0)?;
+            elided = 0;
+            let entry = i.checked_sub(1).map(|i| &self.history.record.entries[i]);
+            self.fmt_graph(f, at, entry, 0)?;
+        }
+        if elided > 0 {
+            self.format.elided(f, elided)?;
+        }
+        Ok(())
+    }
+}
+
+/// Graphviz DOT export of the history tree.
+///
+/// There is one node for each state of the history, where the start state is labeled with the
+/// position only. The edges from a command to the next command are solid and the edges
+/// where a branch forks off are dashed. The current state is bold and the saved state is filled.
+///
+/// # Examples
+/// ```
+/// # use core::fmt;
+/// # use redo::{Command, History};
+/// # struct Add(char);
+/// # impl fmt::Display for Add {
+/// #     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+/// #         write!(f, "Add {}", self.0)
+/// #     }
+/// # }
+/// # impl Command for Add {
+/// #     type Target = String;
+/// #     type Error = &'static str;
+/// #     type Output = ();
+/// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> redo::Result<Add> {
+/// #         self.0 = s.pop().ok_or("s is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
+/// let mut history = History::default();
+/// history.apply(Add('a'))?;
+/// history.apply(Add('b'))?;
+/// history.undo()?;
+/// history.apply(Add('c'))?;
+/// let dot = history.dot().to_string();
+/// assert!(dot.starts_with("digraph {"));
+/// assert!(dot.contains("[label=\"1:2 Add c\", style=bold]"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone)]
+pub struct Dot<'a, C: Command, F, K: Clock> {
+    history: &'a History<C, F, K>,
+    position: bool,
+}
+
+impl<C: Command, F, K: Clock> Dot<'_, C, F, K> {
+    /// Show the position of the command in the labels (on by default).
+    pub fn position(&mut self, on: bool) -> &mut Self {
+        self.position = on;
+        self
+    }
+}
+
+impl<C: Command + fmt::Display, F, K: Clock> Dot<'_, C, F, K> {
+    fn fmt_node(
+        &self,
+        f: &mut fmt::Formatter,
+        at: At,
+        entry: Option<&Entry<C, K::Timestamp>>,
+        saved: Option<At>,
+    ) -> fmt::Result {
+        write!(f, "    n{} [label=\"", self.history.node_at(at).0)?;
+        if self.position {
+            write!(f, "{}:{}", at.branch, at.current)?;
+        }
+        if let Some(entry) = entry {
+            if self.position {
+                f.write_char(' ')?;
+            }
+            for c in entry.to_string().trim().chars() {
+                match c {
+                    '"' => f.write_str("\\\"")?,
+                    '\\' => f.write_str("\\\\")?,
+                    '\n' => f.write_str("\\n")?,
+                    '\r' => (),
+                    c => f.write_char(c)?,
+                }
+            }
+        }
+        f.write_char('"')?;
+        match (at == self.history.at(), saved == Some(at)) {
+            (true, true) => f.write_str(", style=\"bold,filled\"")?,
+            (true, false) => f.write_str(", style=bold")?,
+            (false, true) => f.write_str(", style=filled")?,
+            (false, false) => (),
         }
-        self.fmt_graph(f, At::new(branch, 0), None, 0)
+        f.write_str("];\n")
+    }
+
+    fn fmt_edge(&self, f: &mut fmt::Formatter, from: At, to: At, fork: bool) -> fmt::Result {
+        write!(
+            f,
+            "    n{} -> n{}",
+            self.history.node_at(from).0,
+            self.history.node_at(to).0
+        )?;
+        if fork {
+            f.write_str(" [style=dashed]")?;
+        }
+        f.write_str(";\n")
+    }
+}
+
+impl<'a, C: Command, F, K: Clock> From<&'a History<C, F, K>> for Dot<'a, C, F, K> {
+    fn from(history: &'a History<C, F, K>) -> Self {
+        Dot {
+            history,
+            position: true,
+        }
+    }
+}
+
+impl<C: Command + fmt::Display, F, K: Clock> fmt::Display for Dot<'_, C, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let history = self.history;
+        let root = history.branch();
+        let saved = history
+            .record
+            .saved
+            .map(|saved| At::new(root, saved))
+            .or(history.saved);
+        writeln!(f, "digraph {{")?;
+        self.fmt_node(f, At::new(root, 0), None, saved)?;
+        for (i, entry) in history.record.entries.iter().enumerate() {
+            let at = At::new(root, i + 1);
+            self.fmt_node(f, at, Some(entry), saved)?;
+            self.fmt_edge(f, At::new(root, i), at, false)?;
+        }
+        for (&id, branch) in &history.branches {
+            for (i, entry) in branch.entries.iter().enumerate() {
+                let at = At::new(id, branch.parent.current + i + 1);
+                self.fmt_node(f, at, Some(entry), saved)?;
+                if i == 0 {
+                    self.fmt_edge(f, branch.parent, at, true)?;
+                } else {
+                    self.fmt_edge(f, At::new(id, at.current - 1), at, false)?;
+                }
+            }
+        }
+        f.write_str("}\n")
+    }
+}
+
+/// JSON export of the history.
+///
+/// Contains the same information as the display structure, without requiring the commands or
+/// the target to be serializable. The current branch is first, and the other branches follow in
+/// the order of their identifiers. The commands are written by using their `Display` implementation
+/// and the timestamps are written by [`Timestamp::fmt_json`], as RFC 3339 strings in UTC
+/// for the wall-clock timestamps, as nanoseconds for `Duration`, and as `null` without a clock.
+///
+/// ```json
+/// {
+///   "current": { "branch": 1, "position": 2 },
+///   "saved": { "branch": 0, "position": 2 },
+///   "branches": [
+///     {
+///       "id": 1,
+///       "name": null,
+///       "parent": null,
+///       "entries": [
+///         { "position": 1, "node": 1, "timestamp": null, "command": "Add a" },
+///         { "position": 2, "node": 3, "timestamp": null, "command": "Add c" }
+///       ]
+///     },
+///     {
+///       "id": 0,
+///       "name": "b",
+///       "parent": { "branch": 1, "position": 1 },
+///       "entries": [{ "position": 2, "node": 2, "timestamp": null, "command": "Add b" }]
+///     }
+///   ],
+///   "bookmarks": [{ "name": "a", "branch": 1, "position": 1 }]
+/// }
+/// ```
+///
+/// [`Timestamp::fmt_json`]: ../trait.Timestamp.html#method.fmt_json
+#[derive(Copy, Clone)]
+pub struct Json<'a, C: Command, F, K: Clock> {
+    history: &'a History<C, F, K>,
+}
+
+impl<C: Command + fmt::Display, F, K: Clock> Json<'_, C, F, K> {
+    fn fmt_at(&self, f: &mut fmt::Formatter, at: Option<At>) -> fmt::Result {
+        match at {
+            Some(at) => write!(
+                f,
+                "{{\"branch\":{},\"position\":{}}}",
+                at.branch, at.current
+            ),
+            None => f.write_str("null"),
+        }
+    }
+
+    fn fmt_branch<'b>(
+        &self,
+        f: &mut fmt::Formatter,
+        id: usize,
+        parent: Option<At>,
+        entries: impl Iterator<Item = &'b Entry<C, K::Timestamp>>,
+    ) -> fmt::Result
+    where
+        C: 'b,
+        K::Timestamp: 'b,
+    {
+        write!(f, "{{\"id\":{},\"name\":", id)?;
+        match self.history.branch_name(id) {
+            Some(name) => format::json_string(f, name)?,
+            None => f.write_str("null")?,
+        }
+        f.write_str(",\"parent\":")?;
+        self.fmt_at(f, parent)?;
+        f.write_str(",\"entries\":[")?;
+        let start = parent.map_or(0, |parent| parent.current);
+        for (i, entry) in entries.enumerate() {
+            if i > 0 {
+                f.write_char(',')?;
+            }
+            format::json_entry(f, start + i + 1, entry)?;
+        }
+        f.write_str("]}")
+    }
+}
+
+impl<'a, C: Command, F, K: Clock> From<&'a History<C, F, K>> for Json<'a, C, F, K> {
+    fn from(history: &'a History<C, F, K>) -> Self {
+        Json { history }
+    }
+}
+
+impl<C: Command + fmt::Display, F, K: Clock> fmt::Display for Json<'_, C, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let history = self.history;
+        let root = history.branch();
+        f.write_str("{\"current\":")?;
+        self.fmt_at(f, Some(history.at()))?;
+        f.write_str(",\"saved\":")?;
+        let saved = history
+            .record
+            .saved
+            .map(|saved| At::new(root, saved))
+            .or(history.saved);
+        self.fmt_at(f, saved)?;
+        f.write_str(",\"branches\":[")?;
+        self.fmt_branch(f, root, None, history.record.entries.iter())?;
+        for (&id, branch) in &history.branches {
+            f.write_char(',')?;
+            self.fmt_branch(f, id, Some(branch.parent), branch.entries.iter())?;
+        }
+        f.write_str("],\"bookmarks\":[")?;
+        let bookmarks = history
+            .bookmarks
+            .keys()
+            .filter_map(|name| Some((name, history.bookmark(name)?)));
+        for (i, (name, at)) in bookmarks.enumerate() {
+            if i > 0 {
+                f.write_char(',')?;
+            }
+            f.write_str("{\"name\":")?;
+            format::json_string(f, name)?;
+            write!(f, ",\"branch\":{},\"position\":{}}}", at.branch, at.current)?;
+        }
+        f.write_str("]}")
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::*;
-    use alloc::string::String;
-
+    use crate::{
+        fixtures::{Edit, Spend},
+        *,
+    };
+    use alloc::{
+        rc::Rc,
+        string::{String, ToString},
+        vec::Vec,
+    };
+    use core::{cell::RefCell, fmt, time::Duration};
+
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     struct Add(char);
 
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Add {}", self.0)
+        }
+    }
+
     impl Command for Add {
         type Target = String;
         type Error = &'static str;
+        type Output = ();
 
         fn apply(&mut self, s: &mut String) -> Result<Add> {
             s.push(self.0);
@@ -943,4 +2253,863 @@ mod tests {
         history.go_to(abnpq, 5).unwrap().unwrap();
         assert_eq!(history.target(), "abnpq");
     }
+
+    #[test]
+    fn go_to_error() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        let ab = history.branch();
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.target_mut().clear();
+        // Undoing `c` fails, but the branch that was not reached is kept.
+        assert!(history.go_to(ab, 2).unwrap().is_err());
+        assert_eq!(history.branches().len(), 2);
+        history.target_mut().push_str("ac");
+        history.go_to(ab, 2).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+    }
+
+    #[test]
+    fn saved() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.set_saved(true);
+        let ab = history.branch();
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        let ac = history.branch();
+        assert!(!history.is_saved());
+        history.go_to(ab, 2).unwrap().unwrap();
+        assert!(history.is_saved());
+        history.go_to(ac, 1).unwrap().unwrap();
+        assert!(!history.is_saved());
+        history.go_to(ab, 2).unwrap().unwrap();
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn saved_signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut history = history::Builder::new().build_with(String::new(), move |signal| {
+            if let Signal::Saved(_) = signal {
+                slot.borrow_mut().push(signal);
+            }
+        });
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.set_saved(true);
+        let ab = history.branch();
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        let ac = history.branch();
+        signals.borrow_mut().clear();
+        // The saved state is reported once each time it is reached or left across branches.
+        history.go_to(ab, 2).unwrap().unwrap();
+        assert_eq!(signals.borrow_mut().split_off(0), [Signal::Saved(true)]);
+        history.go_to(ac, 2).unwrap().unwrap();
+        assert_eq!(signals.borrow_mut().split_off(0), [Signal::Saved(false)]);
+        history.go_to(ab, 1).unwrap().unwrap();
+        assert!(signals.borrow().is_empty());
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap();
+        let mut checkpoint = history.checkpoint();
+        checkpoint.apply(Add('c')).unwrap();
+        checkpoint.apply(Add('d')).unwrap();
+        checkpoint.cancel().unwrap();
+        // The commands are undone and the branch they created is removed.
+        assert_eq!(history.target(), "a");
+        assert_eq!(history.branches().len(), 1);
+        history.redo().unwrap();
+        assert_eq!(history.target(), "ab");
+    }
+
+    #[test]
+    fn checkpoint_merged() {
+        let mut history = History::default();
+        history.apply(Edit::Type("a".into())).unwrap();
+        history.apply(Edit::Push("x".into())).unwrap();
+        history.undo().unwrap();
+        let mut checkpoint = history.checkpoint();
+        // The command is not merged into the command applied before the checkpoint.
+        assert_eq!(
+            checkpoint.apply(Edit::Type("b".into())),
+            Ok((2, Outcome::Pushed(1)))
+        );
+        checkpoint.apply(Edit::Push("y".into())).unwrap();
+        checkpoint.undo().unwrap();
+        // The merge moves `y` into a new branch.
+        assert_eq!(
+            checkpoint.apply(Edit::Type("c".into())),
+            Ok((3, Outcome::Merged(1)))
+        );
+        checkpoint.cancel().unwrap();
+        assert_eq!(history.target(), "a");
+        assert_eq!(history.current(), 1);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.branches().len(), 1);
+        history.redo().unwrap();
+        assert_eq!(history.target(), "ax");
+    }
+
+    #[test]
+    fn checkpoint_annulled() {
+        let mut history = History::default();
+        history.apply(Edit::Push("a".into())).unwrap();
+        history.apply(Edit::Push("b".into())).unwrap();
+        history.undo().unwrap();
+        let mut checkpoint = history.checkpoint();
+        // The command applied before the checkpoint is not annulled.
+        assert_eq!(
+            checkpoint.apply(Edit::Pop("a".into())),
+            Ok((0, Outcome::Pushed(1)))
+        );
+        checkpoint.apply(Edit::Push("c".into())).unwrap();
+        checkpoint.apply(Edit::Push("d".into())).unwrap();
+        checkpoint.undo().unwrap();
+        // The annulled command is moved into a new branch together with `d`.
+        assert_eq!(
+            checkpoint.apply(Edit::Pop("c".into())),
+            Ok((0, Outcome::Annulled))
+        );
+        checkpoint.apply(Edit::Push("e".into())).unwrap();
+        assert_eq!(
+            checkpoint.apply(Edit::Pop("e".into())),
+            Ok((0, Outcome::Annulled))
+        );
+        checkpoint.cancel().unwrap();
+        assert_eq!(history.target(), "a");
+        assert_eq!(history.current(), 1);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.branches().len(), 1);
+        history.redo().unwrap();
+        assert_eq!(history.target(), "ab");
+    }
+
+    #[test]
+    fn checkpoint_evicted() {
+        let mut history = history::Builder::new().limit(2).build(String::new());
+        history.apply(Edit::Push("a".into())).unwrap();
+        history.apply(Edit::Push("b".into())).unwrap();
+        history.set_saved(true);
+        let mut checkpoint = history.checkpoint();
+        assert_eq!(
+            checkpoint.apply(Edit::Push("c".into())),
+            Ok((3, Outcome::Evicted(1)))
+        );
+        checkpoint.cancel().unwrap();
+        // The evicted command is gone, but the saved state is still the current one.
+        assert_eq!(history.target(), "ab");
+        assert_eq!(history.current(), 1);
+        assert_eq!(history.len(), 1);
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn go_to_atomic() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        let abc = history.branch();
+        history.undo().unwrap();
+        history.undo().unwrap();
+        history.apply(Add('d')).unwrap();
+        let ad = history.branch();
+        history.go_to(abc, 3).unwrap().unwrap();
+        assert_eq!(history.target(), "abc");
+        history.target_mut().truncate(1);
+        assert_eq!(
+            history.go_to_atomic(ad, 2).unwrap(),
+            Err(Rollback::Restored(
+                Error::new(Op::Undo, At::new(abc, 2), "s is empty").with_steps(1)
+            ))
+        );
+        assert_eq!(history.branch(), abc);
+        assert_eq!(history.current(), 3);
+        assert!(history.go_to_atomic(ad, 3).is_none());
+        assert_eq!(history.current(), 3);
+    }
+
+    #[test]
+    fn go_to_atomic_failed() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut history = history::Builder::new().build_with(5, move |signal| {
+            slot.borrow_mut().push(signal);
+        });
+        history.apply(Spend).unwrap();
+        history.apply(Spend).unwrap();
+        let ab = history.branch();
+        history.undo().unwrap();
+        history.apply(Spend).unwrap();
+        let ac = history.branch();
+        // There is only fuel for the step back to the fork point.
+        *history.target_mut() = 1;
+        signals.borrow_mut().clear();
+        assert_eq!(
+            history.go_to_atomic(ab, 2).unwrap(),
+            Err(Rollback::Failed {
+                error: Error::new(Op::Redo, At::new(ab, 1), "out of fuel").with_steps(1),
+                rollback: Error::new(Op::Redo, At::new(ac, 1), "out of fuel"),
+                at: At::new(ac, 1),
+            })
+        );
+        assert_eq!(history.at(), At::new(ac, 1));
+        assert_eq!(history.target(), &0);
+        // Only the change from where it started to where it ended up is reported.
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::CurrentChanged { old: 2, new: 1 },
+                Signal::Redo(true)
+            ]
+        );
+    }
+
+    #[test]
+    fn outcome() {
+        let mut history = history::Builder::new().saved(false).default();
+        assert_eq!(
+            history.apply(Edit::Push("a".into())),
+            Ok((1, Outcome::Pushed(0)))
+        );
+        assert_eq!(
+            history.apply(Edit::Push("b".into())),
+            Ok((2, Outcome::Pushed(1)))
+        );
+        let old = history.branch();
+        history.undo().unwrap();
+        assert_eq!(
+            history.apply(Edit::Pop("a".into())),
+            Ok((0, Outcome::Annulled))
+        );
+        assert_eq!(history.target(), "");
+        assert_eq!(history.len(), 0);
+        // The annulled command is kept in the branch together with the command after it.
+        history.go_to(old, 2).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history.apply(Edit::Type("c".into())),
+            Ok((3, Outcome::Pushed(2)))
+        );
+        assert_eq!(
+            history.apply(Edit::Type("d".into())),
+            Ok((4, Outcome::Merged(2)))
+        );
+    }
+
+    #[test]
+    fn budget() {
+        let mut history = history::Builder::new().budget(3).default();
+        history.apply(Edit::Push("a".into())).unwrap();
+        history.apply(Edit::Push("b".into())).unwrap();
+        let ab = history.branch();
+        history.undo().unwrap();
+        history.apply(Edit::Push("c".into())).unwrap();
+        let ac = history.branch();
+        history.apply(Edit::Push("d".into())).unwrap();
+        history.apply(Edit::Push("e".into())).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.target(), "acde");
+        // The fork point of `ab` is moved back along with the evicted command.
+        history.go_to(ab, 1).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        history.go_to(ac, 3).unwrap().unwrap();
+        assert_eq!(history.target(), "acde");
+        // The fork point of `ab` is evicted, so the branch is removed.
+        history.apply(Edit::Push("f".into())).unwrap();
+        assert_eq!(history.target(), "acdef");
+        assert!(history.go_to(ab, 1).is_none());
+    }
+
+    #[test]
+    fn prune() {
+        let mut history = history::Builder::new()
+            .max_age(Duration::from_secs(60))
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        let ab = history.branch();
+        history.undo().unwrap();
+        history.clock_mut().advance(Duration::from_secs(30));
+        history.apply(Add('c')).unwrap();
+        let ac = history.branch();
+        history.undo().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.clock_mut().advance(Duration::from_secs(45));
+        history.prune();
+        // `ab` is removed since all of its commands are old, but `ac` is kept.
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.target(), "ad");
+        assert!(history.go_to(ab, 1).is_none());
+        history.go_to(ac, 1).unwrap().unwrap();
+        assert_eq!(history.target(), "ac");
+
+        let mut history = history::Builder::new()
+            .max_age(Duration::from_secs(60))
+            .clock(ManualClock::new(Duration::from_secs(30)))
+            .default();
+        history.apply(Add('a')).unwrap();
+        history.clock_mut().set(Duration::from_secs(0));
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        history.undo().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.undo().unwrap();
+        history.undo().unwrap();
+        history.apply(Add('e')).unwrap();
+        // The branch of `c` is forked from the branch of `bd`.
+        let root = history.branch();
+        let nested = history
+            .branches()
+            .filter(|branch| matches!(branch.parent(), Some(at) if at.branch != root))
+            .count();
+        assert_eq!(nested, 1);
+        history.clock_mut().set(Duration::from_secs(80));
+        history.prune();
+        assert_eq!(history.branches().len(), 1);
+        assert_eq!(history.target(), "ae");
+    }
+
+    #[test]
+    fn time_travel() {
+        let mut history = history::Builder::new()
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        history.apply(Add('a')).unwrap();
+        history.clock_mut().advance(Duration::from_secs(1));
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap();
+        history.clock_mut().advance(Duration::from_secs(1));
+        history.apply(Add('c')).unwrap();
+        let ac = history.branch();
+        history
+            .time_travel(&Duration::from_millis(1500))
+            .unwrap()
+            .unwrap();
+        assert_eq!(history.target(), "ab");
+        assert_ne!(history.branch(), ac);
+        history
+            .time_travel(&Duration::from_secs(3))
+            .unwrap()
+            .unwrap();
+        assert_eq!(history.target(), "ac");
+        assert_eq!(history.branch(), ac);
+        history
+            .time_travel(&Duration::from_secs(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(history.target(), "");
+    }
+
+    #[test]
+    fn earlier_later() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.apply(Add('d')).unwrap();
+        history.go_to(0, 2).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        history.apply(Add('e')).unwrap();
+        let mut states = Vec::new();
+        while history.can_undo() {
+            states.push(history.target().clone());
+            history.earlier().unwrap();
+        }
+        assert_eq!(states, ["abe", "acd", "ac", "ab", "a"]);
+        history.earlier().unwrap();
+        assert_eq!(history.target(), "");
+        for state in states.iter().rev() {
+            history.later().unwrap();
+            assert_eq!(history.target(), state);
+        }
+        history.later().unwrap();
+        assert_eq!(history.target(), "abe");
+    }
+
+    #[test]
+    fn node() {
+        let mut history = history::Builder::new().limit(3).default();
+        let start = history.node();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        let ab = history.node();
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        let ac = history.node();
+        // The identifiers stay valid when the branches are switched.
+        history.go_to_node(ab).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        assert_eq!(history.node(), ab);
+        history.go_to_node(ac).unwrap().unwrap();
+        assert_eq!(history.target(), "ac");
+        history.go_to_node(start).unwrap().unwrap();
+        assert_eq!(history.target(), "");
+        // And when the positions are moved by the limit.
+        history.go_to_node(ac).unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.apply(Add('e')).unwrap();
+        assert_eq!(history.position(ac), Some(At::new(history.branch(), 1)));
+        history.go_to_node(ac).unwrap().unwrap();
+        assert_eq!(history.target(), "ac");
+        history.go_to_node(ab).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        // The identifiers of the removed states are not found.
+        assert!(history.position(start).is_none());
+        assert!(history.go_to_node(start).is_none());
+
+        // A merge changes the state, so the merged command gets a new identifier.
+        let mut history = History::default();
+        history.apply(Edit::Type("a".into())).unwrap();
+        let a = history.node();
+        history.apply(Edit::Type("b".into())).unwrap();
+        assert_ne!(history.node(), a);
+        assert!(history.position(a).is_none());
+        assert_eq!(history.position(history.node()), Some(history.at()));
+    }
+
+    #[test]
+    fn branches() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        let abc = history.branch();
+        history.go_to(abc, 1).unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.set_saved(true);
+        let ad = history.branch();
+        history.undo().unwrap();
+        history.apply(Add('e')).unwrap();
+        let ae = history.branch();
+
+        assert_eq!(history.at(), At::new(ae, 2));
+        let root = history.root();
+        assert_eq!((root.id(), root.parent(), root.len()), (ae, None, 2));
+        let commands: Vec<_> = root.iter().map(|view| view.command().0).collect();
+        assert_eq!(commands, ['a', 'e']);
+        assert!(root.iter().all(|view| view.is_applied()));
+        let mut children: Vec<_> = root.children().collect();
+        children.sort_unstable();
+        assert_eq!(children, [abc, ad]);
+
+        let branch = history.get_branch(abc).unwrap();
+        assert_eq!(branch.parent(), Some(At::new(ae, 1)));
+        let views: Vec<_> = branch
+            .iter()
+            .map(|view| (view.index(), view.command().0, view.is_applied()))
+            .collect();
+        assert_eq!(views, [(1, 'b', false), (2, 'c', false)]);
+        let saved = history.get_branch(ad).unwrap().iter().next().unwrap();
+        assert!(saved.is_saved());
+        assert_eq!(history.position(saved.node()), Some(At::new(ad, 2)));
+        assert!(history.get_branch(100).is_none());
+
+        let ids: Vec<_> = history.branches().map(|branch| branch.id()).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[0], ae);
+    }
+
+    #[test]
+    fn names() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.set_bookmark("first", history.node());
+        history.apply(Add('b')).unwrap();
+        let ab = history.branch();
+        assert_eq!(history.set_branch_name(ab, "main"), None);
+        assert_eq!(history.set_branch_name(100, "none"), None);
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        let ac = history.branch();
+        history.set_branch_name(ac, "topic");
+        assert_eq!(history.find_branch("main"), Some(ab));
+        assert_eq!(history.branch_name(ac), Some("topic"));
+        // The bookmark follows its state when the branches are switched.
+        history.go_to(ab, 2).unwrap().unwrap();
+        assert_eq!(history.bookmark("first"), Some(At::new(ab, 1)));
+        history.go_to_bookmark("first").unwrap().unwrap();
+        assert_eq!(history.target(), "a");
+        assert_eq!(
+            history.bookmarks().collect::<Vec<_>>(),
+            [("first", At::new(ab, 1))]
+        );
+        assert!(history.go_to_bookmark("second").is_none());
+
+        let mut display = history.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        assert_eq!(
+            display.detailed(false).to_string(),
+            "* 0:2 [main] Add b\n\
+             | * 1:2 [topic] Add c\n\
+             |/\n\
+             * 0:1 (current) [first] Add a\n\
+             * 0:0 (saved)"
+        );
+    }
+
+    #[test]
+    fn names_merged() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut history = history::Builder::new().build_with(String::new(), move |signal| {
+            if let Signal::BranchSwitched { .. } | Signal::BranchRemoved(_) = signal {
+                slot.borrow_mut().push(signal);
+            }
+        });
+        history.apply(Edit::Push("a".into())).unwrap();
+        history.apply(Edit::Push("b".into())).unwrap();
+        history.apply(Edit::Push("c".into())).unwrap();
+        let abc = history.branch();
+        history.undo().unwrap();
+        // Annulling the push leaves a new branch without commands after the fork point.
+        history.apply(Edit::Pop("b".into())).unwrap();
+        let a = history.branch();
+        history.set_branch_name(a, "main");
+        history.set_bookmark("a", history.node());
+        signals.borrow_mut().clear();
+        // The branch is merged into the branch it switches to, which takes over its name.
+        history.go_to(abc, 3).unwrap().unwrap();
+        assert_eq!(history.target(), "abc");
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::BranchSwitched { old: a, new: abc },
+                Signal::BranchRemoved(a)
+            ]
+        );
+        assert!(history.get_branch(a).is_none());
+        assert_eq!(history.find_branch("main"), Some(abc));
+        assert_eq!(history.bookmark("a"), Some(At::new(abc, 1)));
+    }
+
+    #[test]
+    fn bookmarks_cleared() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.set_bookmark("a", history.node());
+        history.apply(Add('b')).unwrap();
+        history.set_bookmark("b", history.node());
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.set_bookmark("c", history.node());
+        history.clear();
+        // Only the bookmark of the current state is kept, since it is the start of the history.
+        assert_eq!(
+            history.bookmarks().collect::<Vec<_>>(),
+            [("c", At::new(0, 0))]
+        );
+        assert_eq!(history.remove_bookmark("a"), None);
+        assert_eq!(history.remove_bookmark("b"), None);
+    }
+
+    #[test]
+    fn remove_branches() {
+        let mut history = history::Builder::new()
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        history.apply(Add('a')).unwrap();
+        for c in "bcde".chars() {
+            history.clock_mut().advance(Duration::from_secs(1));
+            history.apply(Add(c)).unwrap();
+            history.undo().unwrap();
+        }
+        // The branches of `b`, `c` and `d` are forked from `ae`.
+        let root = history.branch();
+        let ids: Vec<_> = history
+            .branches()
+            .map(|branch| branch.id())
+            .filter(|&id| id != root)
+            .collect();
+        assert_eq!(ids.len(), 3);
+        history.truncate_branches(2);
+        assert!(history.get_branch(ids[0]).is_none());
+        assert!(history.get_branch(ids[1]).is_some());
+        history.remove_branches_before(&Duration::from_secs(3));
+        assert!(history.get_branch(ids[1]).is_none());
+        assert!(history.get_branch(ids[2]).is_some());
+        assert!(!history.remove_branch(history.branch()));
+        history.remove_other_branches();
+        assert!(history.get_branch(ids[2]).is_none());
+        assert!(!history.remove_branch(ids[2]));
+        assert_eq!(history.branches().len(), 1);
+        assert_eq!(history.target(), "a");
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut history = history::Builder::new().build_with(String::new(), move |signal| {
+            if let Signal::BranchRemoved(_) = signal {
+                slot.borrow_mut().push(signal);
+            }
+        });
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        history.set_saved(true);
+        let abc = history.branch();
+        history.undo().unwrap();
+        history.apply(Add('d')).unwrap();
+        let abd = history.branch();
+        history.go_to(abd, 1).unwrap().unwrap();
+        history.apply(Add('e')).unwrap();
+        let ae = history.branch();
+        history.set_branch_name(abc, "abc");
+        // `abc` is forked from `abd`, so it is removed together with it.
+        assert_eq!(
+            history.get_branch(abc).unwrap().parent().unwrap().branch,
+            abd
+        );
+        assert!(history.remove_branch(abd));
+        assert_eq!(history.branches().len(), 1);
+        assert_eq!(history.find_branch("abc"), None);
+        assert!(history.go_to(abc, 3).is_none());
+        assert_eq!(history.target(), "ae");
+        assert!(!history.is_saved());
+        history.go_to(ae, 0).unwrap().unwrap();
+        assert!(!history.is_saved());
+        let mut signals = signals.borrow_mut().split_off(0);
+        signals.sort_by_key(|signal| match signal {
+            Signal::BranchRemoved(id) => *id,
+            _ => unreachable!(),
+        });
+        assert_eq!(
+            signals,
+            [Signal::BranchRemoved(abc), Signal::BranchRemoved(abd)]
+        );
+    }
+
+    #[test]
+    fn truncate_branches() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        history.undo().unwrap();
+        history.apply(Add('d')).unwrap();
+        let abd = history.branch();
+        let abc = history
+            .branches()
+            .find(|branch| branch.id() != abd)
+            .unwrap()
+            .id();
+        history.go_to(abc, 3).unwrap().unwrap();
+        history.undo().unwrap();
+        history.set_saved(true);
+        history.undo().unwrap();
+        history.apply(Add('e')).unwrap();
+        // `abd` is forked from `abc` but was changed more recently.
+        assert_eq!(
+            history.get_branch(abd).unwrap().parent().unwrap().branch,
+            abc
+        );
+        history.truncate_branches(1);
+        assert!(history.get_branch(abc).is_none());
+        assert_eq!(
+            history.get_branch(abd).unwrap().parent(),
+            Some(At::new(history.branch(), 1))
+        );
+        history.go_to(abd, 3).unwrap().unwrap();
+        assert_eq!(history.target(), "abd");
+        history.undo().unwrap();
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn dot() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.set_saved(true);
+        history.undo().unwrap();
+        history.apply(Add('"')).unwrap();
+        assert_eq!(
+            history.dot().to_string(),
+            "digraph {\n\
+            \x20   n0 [label=\"1:0\"];\n\
+            \x20   n1 [label=\"1:1 Add a\"];\n\
+            \x20   n0 -> n1;\n\
+            \x20   n3 [label=\"1:2 Add \\\"\", style=bold];\n\
+            \x20   n1 -> n3;\n\
+            \x20   n2 [label=\"0:2 Add b\", style=filled];\n\
+            \x20   n1 -> n2 [style=dashed];\n\
+            }\n"
+        );
+        history.go_to(0, 2).unwrap().unwrap();
+        let dot = history.dot().position(false).to_string();
+        assert!(dot.contains("n2 [label=\"Add b\", style=\"bold,filled\"];"));
+        assert!(dot.contains("n0 [label=\"\"];"));
+    }
+
+    #[test]
+    fn json() {
+        let mut history = history::Builder::new().clock(()).default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.set_saved(true);
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.set_branch_name(0, "b");
+        history.set_bookmark("a", NodeId(1));
+        assert_eq!(
+            history.json().to_string(),
+            r#"{"current":{"branch":1,"position":2},"saved":{"branch":0,"position":2},"#
+                .to_string()
+                + r#""branches":[{"id":1,"name":null,"parent":null,"entries":["#
+                + r#"{"position":1,"node":1,"timestamp":null,"command":"Add a"},"#
+                + r#"{"position":2,"node":3,"timestamp":null,"command":"Add c"}]},"#
+                + r#"{"id":0,"name":"b","parent":{"branch":1,"position":1},"entries":["#
+                + r#"{"position":2,"node":2,"timestamp":null,"command":"Add b"}]}],"#
+                + r#""bookmarks":[{"name":"a","branch":1,"position":1}]}"#
+        );
+    }
+
+    #[test]
+    fn style() {
+        let mut history = history::Builder::new()
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        history.apply(Add('a')).unwrap();
+        history.clock_mut().advance(Duration::from_secs(1));
+        history.apply(Add('b')).unwrap();
+        history.set_saved(true);
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        // The labels do not have to be static.
+        let saved = ["on", "disk"].join(" ");
+        let mut style = Style::default();
+        style
+            .mark("o")
+            .edge("│")
+            .fork("╯")
+            .current_label("here")
+            .saved_label(&saved);
+        let mut display = history.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        display
+            .style(style)
+            .timestamp(|timestamp, f| write!(f, "+{}", timestamp.as_secs()));
+        assert_eq!(
+            display.to_string(),
+            "o 1:2 +1 (here)\n│ Add c\n\
+             │ o 0:2 +1 (on disk)\n│ │ Add b\n\
+             │╯\n\
+             o 1:1 +0\n│ Add a\n\
+             o 1:0"
+        );
+    }
+
+    #[cfg(feature = "colored")]
+    #[test]
+    fn empty_palette() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.undo().unwrap();
+        history.apply(Add('b')).unwrap();
+        let mut style = Style::default();
+        style.palette(&[]);
+        let mut display = history.display();
+        display.style(style);
+        // The graph is drawn without colors.
+        assert!(display.to_string().contains("Add a"));
+    }
+
+    #[test]
+    fn window() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        history.apply(Add('d')).unwrap();
+        history.go_to(history.branch(), 1).unwrap().unwrap();
+        history.apply(Add('e')).unwrap();
+        history.apply(Add('f')).unwrap();
+        let mut display = history.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        display.detailed(false).range(1..3);
+        assert_eq!(
+            display.to_string(),
+            "… 1 more …\n\
+             * 1:2 Add e\n\
+             | … 2 more …\n\
+             | * 0:2 Add b\n\
+             |/\n\
+             * 1:1 Add a\n"
+        );
+        display.window(0);
+        assert_eq!(display.to_string(), "* 1:3 (current) Add f\n… 5 more …");
+    }
+
+    #[test]
+    fn branch_signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut history = history::Builder::new().build_with(String::new(), move |signal| {
+            if let Signal::BranchCreated(_)
+            | Signal::BranchSwitched { .. }
+            | Signal::BranchRemoved(_) = signal
+            {
+                slot.borrow_mut().push(signal);
+            }
+        });
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.go_to(0, 2).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        history.clear();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::BranchCreated(1),
+                Signal::BranchSwitched { old: 0, new: 1 },
+                Signal::BranchSwitched { old: 1, new: 0 },
+                Signal::BranchRemoved(1),
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_old() {
+        // Serialized by version 0.41 without chrono, so the commands have no timestamps.
+        let json = r#"{"root":1,"next":2,"saved":{"branch":0,"current":2},"record":{"entries":[{"command":"a"},{"command":"c"}],"target":"ac","current":2,"limit":10,"saved":null,"slot":{}},"branches":{"0":{"parent":{"branch":1,"current":1},"entries":[{"command":"b"}]}}}"#;
+        let history: History<Add> = serde_json::from_str(json).unwrap();
+        let json = serde_json::to_string(&history).unwrap();
+        let mut history: History<Add> = serde_json::from_str(&json).unwrap();
+        assert_eq!(history.branches().len(), 2);
+        assert!(history.branches().all(|branch| branch
+            .iter()
+            .all(|view| view.timestamp() == <DefaultClock as Clock>::Timestamp::epoch())));
+        history.go_to(0, 2).unwrap().unwrap();
+        assert_eq!(history.target(), "ab");
+        assert!(history.is_saved());
+        history.apply(Add('d')).unwrap();
+        // The commands are numbered when read, so each state gets its own node identifier.
+        let seqs = history
+            .branches
+            .values()
+            .flat_map(|branch| &branch.entries)
+            .chain(&history.record.entries)
+            .map(|entry| entry.seq)
+            .collect::<alloc::collections::BTreeSet<_>>();
+        assert_eq!(seqs, (1..=4).collect());
+    }
 }
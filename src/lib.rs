@@ -10,6 +10,7 @@
 //! * [Command](trait.Command.html) provides the base functionality for all commands.
 //! * [Record](struct.Record.html) provides basic linear undo-redo functionality.
 //! * [History](struct.History.html) provides non-linear undo-redo functionality that allows you to jump between different branches.
+//! * [Boxed](struct.Boxed.html) allows commands of different types to be stored together by using dynamic dispatch.
 //! * Queue wraps a record or history and extends them with queue functionality.
 //! * Checkpoint wraps a record or history and extends them with checkpoint functionality.
 //! * Commands can be merged into a single command by implementing the
@@ -18,14 +19,23 @@
 //!   merged into larger changes that can be undone and redone in a single step.
 //! * The target can be marked as being saved to disk and the data-structures can track the saved state and notify
 //!   when it changes.
-//! * The amount of changes being tracked can be configured by the user so only the `N` most recent changes are stored.
-//! * Configurable display formatting using the display structure.
+//! * The amount of changes being tracked can be configured by the user so only the `N` most recent changes,
+//!   or the most recent changes that fit in a heap size budget, are stored.
+//! * The commands are time stamped by a [Clock](trait.Clock.html) that can be set in the builders,
+//!   which enables time travel, max ages and merge windows, also in `no_std` builds.
+//! * Configurable display formatting using the display structure, with custom symbols, labels and colors set by a
+//!   [Style](struct.Style.html).
+//! * The history tree can be exported in the Graphviz DOT format for rendering with Graphviz.
+//! * The records and histories can be exported as JSON without requiring the commands to be serializable.
 //! * The library can be used as `no_std` by default.
 //!
 //! # Cargo Feature Flags
 //!
-//! * `chrono`: Enables time stamps and time travel.
+//! * `chrono`: Enables the [`ChronoClock`](struct.ChronoClock.html) and uses it as the default clock.
+//! * `derive`: Enables the derive macro for implementing `Command` on enums of commands.
 //! * `serde`: Enables serialization and deserialization.
+//! * `time`: Enables the [`TimeClock`](struct.TimeClock.html) and uses it as the default clock,
+//!   unless `chrono` is enabled.
 
 #![no_std]
 #![doc(html_root_url = "https://docs.rs/redo")]
@@ -34,19 +44,34 @@
 
 extern crate alloc;
 
+mod boxed;
+mod clock;
+#[cfg(test)]
+mod fixtures;
 mod format;
 pub mod history;
 pub mod record;
 
-#[cfg(feature = "chrono")]
-use chrono::{DateTime, Utc};
+use alloc::vec::Vec;
 use core::fmt;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-pub use self::{history::History, record::Record};
+#[cfg(feature = "chrono")]
+pub use self::clock::ChronoClock;
+#[cfg(feature = "time")]
+pub use self::clock::TimeClock;
+pub use self::{
+    boxed::{Boxed, DynCommand},
+    clock::{Clock, DefaultClock, ManualClock, Timestamp},
+    format::Style,
+    history::History,
+    record::Record,
+};
+#[cfg(feature = "derive")]
+pub use redo_derive::Command;
 
-/// A specialized Result type for undo-redo operations.
+/// A specialized Result type for the methods of the commands.
 pub type Result<C> = core::result::Result<(), <C as Command>::Error>;
 
 /// Base functionality for all commands.
@@ -55,10 +80,20 @@ pub trait Command: Sized {
     type Target;
     /// The error type.
     type Error;
+    /// The output type.
+    ///
+    /// Returned by [`apply`], and by the `apply` methods of the records and histories.
+    /// Use `()` if the command does not produce a value.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    type Output;
 
-    /// Applies the command on the target and returns `Ok` if everything went fine,
+    /// Applies the command on the target and returns `Ok` with the output if everything went fine,
     /// and `Err` if something went wrong.
-    fn apply(&mut self, target: &mut Self::Target) -> Result<Self>;
+    fn apply(
+        &mut self,
+        target: &mut Self::Target,
+    ) -> core::result::Result<Self::Output, Self::Error>;
 
     /// Restores the state of the target as it was before the command was applied
     /// and returns `Ok` if everything went fine, and `Err` if something went wrong.
@@ -67,23 +102,35 @@ pub trait Command: Sized {
     /// Reapplies the command on the target and return `Ok` if everything went fine,
     /// and `Err` if something went wrong.
     ///
-    /// The default implementation uses the [`apply`] implementation.
+    /// The default implementation uses the [`apply`] implementation and discards the output.
     ///
     /// [`apply`]: trait.Command.html#tymethod.apply
     fn redo(&mut self, target: &mut Self::Target) -> Result<Self> {
-        self.apply(target)
+        self.apply(target).map(|_| ())
     }
 
     /// Used for manual merging of commands.
     fn merge(&mut self, command: Self) -> Merge<Self> {
         Merge::No(command)
     }
+
+    /// Returns the approximate number of bytes the command has allocated on the heap.
+    ///
+    /// Used by the records and histories to keep the commands within the budget set
+    /// in their builders. The default implementation returns `0`.
+    fn heap_size(&self) -> usize {
+        0
+    }
 }
 
 /// The signal used for communicating state changes.
 ///
 /// For example, if the record can no longer redo any commands, it sends a `Redo(false)`
 /// signal to tell the user.
+///
+/// The `Undo`, `Redo` and `Saved` signals report the state of the structures, while the other
+/// signals report the changes made to them. The builders can be configured to only emit the
+/// state signals, by turning the `events` option off.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum Signal {
@@ -93,6 +140,46 @@ pub enum Signal {
     Redo(bool),
     /// Says if the target is in a saved state.
     Saved(bool),
+    /// The command at the index has been applied and pushed on top of the current branch.
+    Applied(usize),
+    /// The command at the index has been undone.
+    Undone(usize),
+    /// The command at the index has been redone.
+    Redone(usize),
+    /// The applied command has been merged into the command at the index.
+    Merged(usize),
+    /// The applied command and the command before it have cancelled each other out,
+    /// and both have been removed.
+    Annulled,
+    /// The oldest command has been removed because the limit or the budget was reached.
+    EntryEvicted,
+    /// All the commands have been removed.
+    Cleared,
+    /// The current position has changed.
+    CurrentChanged {
+        /// The previous position.
+        old: usize,
+        /// The new position.
+        new: usize,
+    },
+    /// A new branch has been created in the history.
+    BranchCreated(usize),
+    /// The current branch of the history has changed.
+    BranchSwitched {
+        /// The previous branch.
+        old: usize,
+        /// The new branch.
+        new: usize,
+    },
+    /// The branch has been removed from the history.
+    BranchRemoved(usize),
+}
+
+impl Signal {
+    /// Returns `true` if the signal is one of the `Undo`, `Redo` or `Saved` signals.
+    pub fn is_state(&self) -> bool {
+        matches!(self, Signal::Undo(_) | Signal::Redo(_) | Signal::Saved(_))
+    }
 }
 
 /// Says if the command have been merged with another command.
@@ -107,31 +194,252 @@ pub enum Merge<C> {
     Annul,
 }
 
+/// Says what happened to a command when it was applied.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Outcome {
+    /// The command was pushed at the index.
+    Pushed(usize),
+    /// The command was merged into the command at the index.
+    Merged(usize),
+    /// The command and the command before it cancelled each other out, and both were removed.
+    Annulled,
+    /// The command was pushed at the index, and the oldest commands were removed
+    /// because the limit or the budget was reached.
+    Evicted(usize),
+}
+
+/// The operation that failed.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Op {
+    /// The [`apply`](trait.Command.html#tymethod.apply) method of a command failed.
+    Apply,
+    /// The [`undo`](trait.Command.html#tymethod.undo) method of a command failed.
+    Undo,
+    /// The [`redo`](trait.Command.html#method.redo) method of a command failed.
+    Redo,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Op::Apply => "apply",
+            Op::Undo => "undo",
+            Op::Redo => "redo",
+        })
+    }
+}
+
+/// The error returned by the records and histories.
+///
+/// Wraps the error returned by the command together with the operation that failed,
+/// the position the structure was at when it failed, and the number of steps
+/// a multi-step operation, like `go_to` or `Queue::commit`, completed before it failed.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Error<E> {
+    op: Op,
+    at: At,
+    steps: usize,
+    error: E,
+}
+
+impl<E> Error<E> {
+    fn new(op: Op, at: At, error: E) -> Error<E> {
+        Error {
+            op,
+            at,
+            steps: 0,
+            error,
+        }
+    }
+
+    fn with_steps(mut self, steps: usize) -> Error<E> {
+        self.steps = steps;
+        self
+    }
+
+    fn on_branch(mut self, branch: usize) -> Error<E> {
+        self.at.branch = branch;
+        self
+    }
+
+    /// Returns the operation that failed.
+    pub fn op(&self) -> Op {
+        self.op
+    }
+
+    /// Returns the position the structure was at when the operation failed.
+    pub fn at(&self) -> At {
+        self.at
+    }
+
+    /// Returns the number of steps that were completed before the operation failed.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Returns a reference to the error returned by the command.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error returned by the command.
+    pub fn into_error(self) -> E {
+        self.error
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} failed at {}:{}",
+            self.op, self.at.branch, self.at.current
+        )?;
+        match self.steps {
+            0 => {}
+            1 => f.write_str(" after 1 step")?,
+            steps => write!(f, " after {} steps", steps)?,
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// The error returned when an atomic operation fails.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Rollback<E> {
+    /// The operation failed and the structure was restored to the position it started at.
+    Restored(E),
+    /// The operation failed, and restoring the starting position failed as well.
+    Failed {
+        /// The error that made the operation fail.
+        error: E,
+        /// The error that made the restoration fail.
+        rollback: E,
+        /// The position the structure ended up at.
+        at: At,
+    },
+}
+
+impl<E> Rollback<E> {
+    /// Returns the error that made the operation fail.
+    pub fn into_error(self) -> E {
+        match self {
+            Rollback::Restored(error) | Rollback::Failed { error, .. } => error,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Rollback<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rollback::Restored(error) => write!(f, "{} (restored)", error),
+            Rollback::Failed {
+                error,
+                rollback,
+                at,
+            } => write!(
+                f,
+                "{} (restoring failed at {}:{}: {})",
+                error, at.branch, at.current, rollback
+            ),
+        }
+    }
+}
+
 /// A position in a history tree.
+///
+/// Positions in a record are always in branch `0`.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
-struct At {
-    branch: usize,
-    current: usize,
+pub struct At {
+    /// The branch of the position.
+    pub branch: usize,
+    /// The position in the branch.
+    pub current: usize,
 }
 
 impl At {
-    fn new(branch: usize, current: usize) -> At {
+    /// Returns a new position.
+    pub fn new(branch: usize, current: usize) -> At {
         At { branch, current }
     }
 }
 
+/// A stable identifier of a state in a record or history.
+///
+/// Unlike the positions, which change when branches are switched or commands are removed,
+/// the identifier of a state stays the same for as long as the state exists.
+/// A command that is merged into the last command creates a new state with a new identifier.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct NodeId(usize);
+
+/// A handle to a slot that has been subscribed to a record or history.
+///
+/// Can be used to unsubscribe the slot again.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Subscription(usize);
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Slot<F> {
     #[cfg_attr(feature = "serde", serde(default = "Option::default", skip))]
     f: Option<F>,
+    #[cfg_attr(feature = "serde", serde(default = "Vec::default", skip))]
+    subscribers: Vec<(Subscription, F)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    next: usize,
+    #[cfg_attr(feature = "serde", serde(default = "crate::enabled"))]
+    events: bool,
+}
+
+/// The serde default of the options that are enabled unless they are turned off.
+#[cfg(feature = "serde")]
+fn enabled() -> bool {
+    true
+}
+
+impl<F> Slot<F> {
+    fn new(f: Option<F>, events: bool) -> Slot<F> {
+        Slot {
+            f,
+            subscribers: Vec::new(),
+            next: 0,
+            events,
+        }
+    }
+
+    fn subscribe(&mut self, f: F) -> Subscription {
+        let subscription = Subscription(self.next);
+        self.next += 1;
+        self.subscribers.push((subscription, f));
+        subscription
+    }
+
+    fn unsubscribe(&mut self, subscription: Subscription) -> Option<F> {
+        let i = self
+            .subscribers
+            .iter()
+            .position(|&(s, _)| s == subscription)?;
+        Some(self.subscribers.remove(i).1)
+    }
 }
 
 impl<F: FnMut(Signal)> Slot<F> {
     fn emit(&mut self, signal: Signal) {
+        if !self.events && !signal.is_state() {
+            return;
+        }
         if let Some(ref mut f) = self.f {
             f(signal);
         }
+        for (_, f) in &mut self.subscribers {
+            f(signal);
+        }
     }
 
     fn emit_if(&mut self, cond: bool, signal: Signal) {
@@ -143,42 +451,66 @@ impl<F: FnMut(Signal)> Slot<F> {
 
 impl<F> Default for Slot<F> {
     fn default() -> Self {
-        Slot { f: None }
+        Slot::new(None, true)
     }
 }
 
 impl<F> fmt::Debug for Slot<F> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.f {
-            Some(_) => f.pad("Slot { .. }"),
-            None => f.pad("Empty"),
+        match (&self.f, self.subscribers.len()) {
+            (None, 0) => f.pad("Empty"),
+            (_, subscribers) => f
+                .debug_struct("Slot")
+                .field("connected", &self.f.is_some())
+                .field("subscribers", &subscribers)
+                .finish(),
         }
     }
 }
 
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(bound(deserialize = "C: Deserialize<'de>, T: Timestamp + Deserialize<'de>"))
+)]
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
-struct Entry<C> {
+struct Entry<C, T> {
     command: C,
-    #[cfg(feature = "chrono")]
-    timestamp: DateTime<Utc>,
+    /// Set to the epoch for data serialized by an earlier version, which did not always store it.
+    #[cfg_attr(feature = "serde", serde(default = "Timestamp::epoch"))]
+    timestamp: T,
+    /// The order the command was applied in, counted across all branches.
+    /// Also used as the identifier of the state right after the command.
+    #[cfg_attr(feature = "serde", serde(default))]
+    seq: usize,
 }
 
-impl<C> From<C> for Entry<C> {
-    fn from(command: C) -> Self {
+impl<C, T> Entry<C, T> {
+    fn new(command: C, timestamp: T, seq: usize) -> Entry<C, T> {
         Entry {
             command,
-            #[cfg(feature = "chrono")]
-            timestamp: Utc::now(),
+            timestamp,
+            seq,
+        }
+    }
+
+    /// Gives the entry the next sequence number if it was serialized by an earlier version,
+    /// which did not store them.
+    #[cfg(feature = "serde")]
+    fn renumber(&mut self, seq: &mut usize) {
+        if self.seq == 0 {
+            self.seq = *seq;
+            *seq += 1;
         }
     }
 }
 
-impl<C: Command> Command for Entry<C> {
+impl<C: Command, T> Command for Entry<C, T> {
     type Target = C::Target;
     type Error = C::Error;
+    type Output = C::Output;
 
-    fn apply(&mut self, target: &mut Self::Target) -> Result<Self> {
+    fn apply(&mut self, target: &mut Self::Target) -> core::result::Result<C::Output, C::Error> {
         self.command.apply(target)
     }
 
@@ -190,16 +522,20 @@ impl<C: Command> Command for Entry<C> {
         self.command.redo(target)
     }
 
-    fn merge(&mut self, command: Self) -> Merge<Self> {
-        match self.command.merge(command.command) {
+    fn merge(&mut self, other: Self) -> Merge<Self> {
+        match self.command.merge(other.command) {
             Merge::Yes => Merge::Yes,
-            Merge::No(command) => Merge::No(Entry::from(command)),
+            Merge::No(command) => Merge::No(Entry::new(command, other.timestamp, other.seq)),
             Merge::Annul => Merge::Annul,
         }
     }
+
+    fn heap_size(&self) -> usize {
+        self.command.heap_size()
+    }
 }
 
-impl<C: fmt::Display> fmt::Display for Entry<C> {
+impl<C: fmt::Display, T> fmt::Display for Entry<C, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         (&self.command as &dyn fmt::Display).fmt(f)
     }
@@ -1,22 +1,26 @@
 //! A record of commands.
 
-use crate::{format::Format, At, Command, Entry, History, Merge, Result, Signal, Slot};
+use crate::{
+    format::{self, Format, Formatted, Style, TimeFormat},
+    At, Clock, Command, DefaultClock, Entry, Error, History, Merge, NodeId, Op, Outcome, Rollback,
+    Signal, Slot, Subscription, Timestamp,
+};
 use alloc::{
-    collections::VecDeque,
+    collections::{vec_deque, VecDeque},
     string::{String, ToString},
     vec::Vec,
 };
 use core::{
+    cmp::Ordering,
     fmt::{self, Write},
+    iter::{Enumerate, FusedIterator},
+    mem,
     num::NonZeroUsize,
+    ops::RangeBounds,
+    time::Duration,
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "chrono")]
-use {
-    chrono::{DateTime, TimeZone, Utc},
-    core::cmp::Ordering,
-};
 
 /// A record of commands.
 ///
@@ -25,6 +29,7 @@ use {
 /// about changes to the stack or the target through [signal](enum.Signal.html).
 /// The user can give the record a function that is called each time the state
 /// changes by using the [`builder`](struct.RecordBuilder.html).
+/// The commands are time stamped by the [clock](../trait.Clock.html) of the record.
 ///
 /// # Examples
 /// ```
@@ -33,6 +38,7 @@ use {
 /// # impl Command for Add {
 /// #     type Target = String;
 /// #     type Error = &'static str;
+/// #     type Output = ();
 /// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
 /// #         s.push(self.0);
 /// #         Ok(())
@@ -42,7 +48,7 @@ use {
 /// #         Ok(())
 /// #     }
 /// # }
-/// # fn main() -> redo::Result<Add> {
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
 /// let mut record = Record::default();
 /// record.apply(Add('a'))?;
 /// record.apply(Add('b'))?;
@@ -62,18 +68,86 @@ use {
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
-    serde(bound(
-        serialize = "C: Command + Serialize, C::Target: Serialize",
-        deserialize = "C: Command + Deserialize<'de>, C::Target: Deserialize<'de>"
-    ))
+    serde(
+        from = "RecordData<C, F, K>",
+        bound(
+            serialize = "C: Command + Serialize, C::Target: Serialize, K: Serialize, K::Timestamp: Serialize",
+            deserialize = "C: Command + Deserialize<'de>, C::Target: Deserialize<'de>, K: Default + Deserialize<'de>, K::Timestamp: Deserialize<'de>"
+        )
+    )
 )]
-pub struct Record<C: Command, F = fn(Signal)> {
-    pub(crate) entries: VecDeque<Entry<C>>,
+pub struct Record<C: Command, F = fn(Signal), K: Clock = DefaultClock> {
+    pub(crate) entries: VecDeque<Entry<C, K::Timestamp>>,
     target: C::Target,
     current: usize,
     limit: NonZeroUsize,
+    budget: Option<usize>,
+    max_age: Option<Duration>,
+    merge_window: Option<Duration>,
     pub(crate) saved: Option<usize>,
     pub(crate) slot: Slot<F>,
+    clock: K,
+    // The identifier of the state at the start of the record.
+    start: usize,
+    // The sequence number of the next command, starting at 1 since 0 is the initial state.
+    pub(crate) seq: usize,
+    // The sum of the heap size of the commands, kept up to date so the budget is cheap to check.
+    // Not serialized, since it is summed up again when read.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    heap_size: usize,
+}
+
+/// The serialized form of a record, which may come from an earlier version.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(
+    deserialize = "C: Command + Deserialize<'de>, C::Target: Deserialize<'de>, K: Default + Deserialize<'de>, K::Timestamp: Deserialize<'de>"
+))]
+struct RecordData<C: Command, F, K: Clock> {
+    entries: VecDeque<Entry<C, K::Timestamp>>,
+    target: C::Target,
+    current: usize,
+    limit: NonZeroUsize,
+    #[serde(default)]
+    budget: Option<usize>,
+    #[serde(default)]
+    max_age: Option<Duration>,
+    #[serde(default)]
+    merge_window: Option<Duration>,
+    saved: Option<usize>,
+    slot: Slot<F>,
+    #[serde(default)]
+    clock: K,
+    #[serde(default)]
+    start: usize,
+    #[serde(default)]
+    seq: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<C: Command, F, K: Clock> From<RecordData<C, F, K>> for Record<C, F, K> {
+    fn from(data: RecordData<C, F, K>) -> Record<C, F, K> {
+        let mut record = Record {
+            heap_size: data.entries.iter().map(Entry::heap_size).sum(),
+            entries: data.entries,
+            target: data.target,
+            current: data.current,
+            limit: data.limit,
+            budget: data.budget,
+            max_age: data.max_age,
+            merge_window: data.merge_window,
+            saved: data.saved,
+            slot: data.slot,
+            clock: data.clock,
+            start: data.start,
+            // Data from an earlier version has no sequence numbers, and 0 is the initial state.
+            seq: data.seq.max(1),
+        };
+        for entry in &mut record.entries {
+            entry.renumber(&mut record.seq);
+        }
+        record
+    }
 }
 
 impl<C: Command> Record<C> {
@@ -83,7 +157,7 @@ impl<C: Command> Record<C> {
     }
 }
 
-impl<C: Command, F> Record<C, F> {
+impl<C: Command, F, K: Clock> Record<C, F, K> {
     /// Reserves capacity for at least `additional` more commands.
     ///
     /// # Panics
@@ -117,6 +191,38 @@ impl<C: Command, F> Record<C, F> {
         self.limit.get()
     }
 
+    /// Returns the heap size budget of the record in bytes, if it has one.
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Returns the max age of the commands in the record, if it has one.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Returns the merge window of the record, if it has one.
+    pub fn merge_window(&self) -> Option<Duration> {
+        self.merge_window
+    }
+
+    /// Returns the sum of the [`heap_size`] of the commands in the record.
+    ///
+    /// [`heap_size`]: ../trait.Command.html#method.heap_size
+    pub fn heap_size(&self) -> usize {
+        self.heap_size
+    }
+
+    /// Returns a reference to the clock of the record.
+    pub fn clock(&self) -> &K {
+        &self.clock
+    }
+
+    /// Returns a mutable reference to the clock of the record.
+    pub fn clock_mut(&mut self) -> &mut K {
+        &mut self.clock
+    }
+
     /// Sets how the signal should be handled when the state changes.
     ///
     /// The previous slot is returned if it exists.
@@ -129,6 +235,47 @@ impl<C: Command, F> Record<C, F> {
         self.slot.f.take()
     }
 
+    /// Adds a slot that is called each time the state changes, in addition to the connected slot.
+    ///
+    /// Any number of slots can be subscribed at the same time, and they are called in the order
+    /// they were subscribed. Use a boxed closure as `F` to subscribe closures of different types.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command for Add {
+    /// #     type Target = String;
+    /// #     type Error = &'static str;
+    /// #     type Output = ();
+    /// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> redo::Result<Add> {
+    /// #         self.0 = s.pop().ok_or("s is empty")?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// # fn main() -> Result<(), redo::Error<&'static str>> {
+    /// let mut record = Record::default();
+    /// let toolbar = record.subscribe(|signal| println!("toolbar: {:?}", signal));
+    /// let title = record.subscribe(|signal| println!("title: {:?}", signal));
+    /// record.apply(Add('a'))?;
+    /// assert!(record.unsubscribe(toolbar).is_some());
+    /// assert!(record.unsubscribe(toolbar).is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe(&mut self, slot: F) -> Subscription {
+        self.slot.subscribe(slot)
+    }
+
+    /// Removes and returns the subscribed slot if it exists.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> Option<F> {
+        self.slot.unsubscribe(subscription)
+    }
+
     /// Returns `true` if the record can undo.
     pub fn can_undo(&self) -> bool {
         self.current() > 0
@@ -141,7 +288,7 @@ impl<C: Command, F> Record<C, F> {
 
     /// Returns `true` if the target is in a saved state, `false` otherwise.
     pub fn is_saved(&self) -> bool {
-        self.saved.map_or(false, |saved| saved == self.current())
+        self.saved == Some(self.current())
     }
 
     /// Returns the position of the current command.
@@ -149,21 +296,79 @@ impl<C: Command, F> Record<C, F> {
         self.current
     }
 
+    /// Returns the identifier of the current state.
+    pub fn node(&self) -> NodeId {
+        self.node_at(self.current)
+    }
+
+    /// Returns the position of the state with the identifier,
+    /// or `None` if the state has been removed from the record.
+    pub fn position(&self, node: NodeId) -> Option<usize> {
+        if node.0 == self.start {
+            return Some(0);
+        }
+        let i = self.entries.iter().position(|entry| entry.seq == node.0)?;
+        Some(i + 1)
+    }
+
+    /// Removes and returns the commands from position `current` and onwards.
+    pub(crate) fn split_off(&mut self, current: usize) -> VecDeque<Entry<C, K::Timestamp>> {
+        let tail = self.entries.split_off(current);
+        self.heap_size -= tail.iter().map(Entry::heap_size).sum::<usize>();
+        tail
+    }
+
+    /// Moves all the commands in `entries` to the end of the record.
+    pub(crate) fn append(&mut self, entries: &mut VecDeque<Entry<C, K::Timestamp>>) {
+        self.heap_size += entries.iter().map(Entry::heap_size).sum::<usize>();
+        self.entries.append(entries);
+    }
+
+    /// Adds the command after the newest command.
+    pub(crate) fn push_back(&mut self, entry: Entry<C, K::Timestamp>) {
+        self.heap_size += entry.heap_size();
+        self.entries.push_back(entry);
+    }
+
+    /// Removes and returns the newest command.
+    pub(crate) fn pop_back(&mut self) -> Option<Entry<C, K::Timestamp>> {
+        let entry = self.entries.pop_back()?;
+        self.heap_size -= entry.heap_size();
+        Some(entry)
+    }
+
+    pub(crate) fn node_at(&self, current: usize) -> NodeId {
+        match current.checked_sub(1) {
+            Some(i) => NodeId(self.entries[i].seq),
+            None => NodeId(self.start),
+        }
+    }
+
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<C, F> {
+    pub fn queue(&mut self) -> Queue<'_, C, F, K> {
         Queue::from(self)
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<C, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, C, F, K> {
         Checkpoint::from(self)
     }
 
     /// Returns a structure for configurable formatting of the record.
-    pub fn display(&self) -> Display<C, F> {
+    pub fn display(&self) -> Display<'_, C, F, K> {
         Display::from(self)
     }
 
+    /// Returns a structure for exporting the record as JSON.
+    pub fn json(&self) -> Json<'_, C, F, K> {
+        Json::from(self)
+    }
+
+    /// Returns an iterator over the commands in the record, from the oldest to the newest.
+    pub fn iter(&self) -> Iter<'_, C, K::Timestamp> {
+        Iter::new(&self.entries, 0, self.current, self.saved)
+    }
+
     /// Returns a reference to the `target`.
     pub fn target(&self) -> &C::Target {
         &self.target
@@ -182,7 +387,7 @@ impl<C: Command, F> Record<C, F> {
     }
 }
 
-impl<C: Command, F: FnMut(Signal)> Record<C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> Record<C, F, K> {
     /// Marks the target as currently being in a saved or unsaved state.
     pub fn set_saved(&mut self, saved: bool) {
         let was_saved = self.is_saved();
@@ -196,72 +401,197 @@ impl<C: Command, F: FnMut(Signal)> Record<C, F> {
     }
 
     /// Revert the changes done to the target since the saved state.
-    pub fn revert(&mut self) -> Option<Result<C>> {
+    pub fn revert(&mut self) -> Option<Result<(), Error<C::Error>>> {
         self.saved.and_then(|saved| self.go_to(saved))
     }
 
     /// Removes all commands from the record without undoing them.
     pub fn clear(&mut self) {
+        let old = self.current();
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
+        // The current state is the start of the record from now on.
+        self.start = self.node().0;
         self.entries.clear();
+        self.heap_size = 0;
         self.saved = if self.is_saved() { Some(0) } else { None };
         self.current = 0;
+        self.slot.emit(Signal::Cleared);
+        self.slot
+            .emit_if(old != 0, Signal::CurrentChanged { old, new: 0 });
         self.slot.emit_if(could_undo, Signal::Undo(false));
         self.slot.emit_if(could_redo, Signal::Redo(false));
     }
 
     /// Pushes the command on top of the record and executes its [`apply`] method.
     ///
+    /// Returns the output of the command, and if it was pushed, merged or annulled.
+    ///
     /// # Errors
     /// If an error occur when executing [`apply`] the error is returned.
     ///
     /// [`apply`]: trait.Command.html#tymethod.apply
-    pub fn apply(&mut self, command: C) -> Result<C> {
-        self.__apply(command).map(|_| ())
+    pub fn apply(&mut self, command: C) -> Result<(C::Output, Outcome), Error<C::Error>> {
+        self.__apply(command, true)
+            .map(|(output, outcome, _, _)| (output, outcome))
     }
 
+    /// Applies the command, merging it into the command before it only if `merge` is `true`.
+    ///
+    /// Returns the number of evicted commands and the commands after the current position,
+    /// with an annulled command at the front.
+    #[allow(clippy::type_complexity)]
     pub(crate) fn __apply(
         &mut self,
         mut command: C,
-    ) -> core::result::Result<(bool, VecDeque<Entry<C>>), C::Error> {
-        command.apply(&mut self.target)?;
+        merge: bool,
+    ) -> Result<(C::Output, Outcome, usize, VecDeque<Entry<C, K::Timestamp>>), Error<C::Error>>
+    {
         let current = self.current();
+        let output = command
+            .apply(&mut self.target)
+            .map_err(|error| Error::new(Op::Apply, At::new(0, current), error))?;
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         let was_saved = self.is_saved();
         // Pop off all elements after len from record.
-        let tail = self.entries.split_off(current);
+        let mut tail = self.split_off(current);
         // Check if the saved state was popped off.
         self.saved = self.saved.filter(|&saved| saved <= current);
-        // Try to merge commands unless the target is in a saved state.
+        let mut evicted = 0;
+        let now = self.clock.now();
+        // Check if the merge window has passed since the last command was applied.
+        let idle = match (self.merge_window, self.entries.back()) {
+            (Some(window), Some(last)) => now.duration_since(&last.timestamp) > window,
+            _ => false,
+        };
+        // Try to merge commands unless the target is in a saved state or the record has been idle.
         let merged = match self.entries.back_mut() {
-            Some(ref mut last) if !was_saved => last.command.merge(command),
+            Some(ref mut last) if merge && !was_saved && !idle => {
+                let heap_size = last.heap_size();
+                let merged = last.command.merge(command);
+                self.heap_size = self.heap_size - heap_size + last.heap_size();
+                merged
+            }
             _ => Merge::No(command),
         };
-        let merged_or_annulled = match merged {
-            Merge::Yes => true,
+        let outcome = match merged {
+            Merge::Yes => {
+                // The merge window starts over from the merged command, which was last changed now.
+                let last = self.entries.back_mut().unwrap();
+                if self.merge_window.is_some() {
+                    last.timestamp = now;
+                }
+                // The merge changes the state, so the identifier of the old state is no longer valid.
+                last.seq = self.seq;
+                self.seq += 1;
+                self.slot.emit(Signal::Merged(current - 1));
+                Outcome::Merged(current - 1)
+            }
             Merge::Annul => {
-                self.entries.pop_back();
-                true
+                let entry = self.pop_back().unwrap();
+                // Keep the annulled command together with the commands that were applied after it.
+                tail.push_front(entry);
+                self.current -= 1;
+                self.slot.emit(Signal::Annulled);
+                Outcome::Annulled
             }
             // If commands are not merged or annulled push it onto the record.
             Merge::No(command) => {
                 // If limit is reached, pop off the first command.
                 if self.limit() == self.current() {
-                    self.entries.pop_front();
-                    self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
-                } else {
-                    self.current += 1;
+                    self.evict();
+                    evicted += 1;
                 }
-                self.entries.push_back(Entry::from(command));
-                false
+                self.current += 1;
+                self.push_back(Entry::new(command, now, self.seq));
+                self.seq += 1;
+                self.slot.emit(Signal::Applied(self.current - 1));
+                Outcome::Pushed(self.current - 1)
+            }
+        };
+        // If the budget is exceeded, pop off the oldest commands but keep the newest one.
+        if let Some(budget) = self.budget {
+            while self.heap_size > budget && self.len() > 1 {
+                self.evict();
+                evicted += 1;
             }
+        }
+        evicted += self.evict_expired(self.current().saturating_sub(1));
+        let outcome = match outcome {
+            Outcome::Pushed(_) if evicted > 0 => Outcome::Evicted(self.current - 1),
+            Outcome::Merged(_) => Outcome::Merged(self.current - 1),
+            outcome => outcome,
         };
+        let can_undo = self.can_undo();
+        let is_saved = self.is_saved();
+        self.slot.emit_if(
+            current != self.current,
+            Signal::CurrentChanged {
+                old: current,
+                new: self.current,
+            },
+        );
         self.slot.emit_if(could_redo, Signal::Redo(false));
-        self.slot.emit_if(!could_undo, Signal::Undo(true));
-        self.slot.emit_if(was_saved, Signal::Saved(false));
-        Ok((merged_or_annulled, tail))
+        self.slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        Ok((output, outcome, evicted, tail))
+    }
+
+    /// Returns `true` if the command before the current position was applied
+    /// after the command with the sequence number `seq`, or is that command.
+    pub(crate) fn applied_since(&self, seq: usize) -> bool {
+        self.current > 0 && self.entries[self.current - 1].seq >= seq
+    }
+
+    /// Pops off the oldest command.
+    fn evict(&mut self) {
+        let entry = self.entries.pop_front().unwrap();
+        self.heap_size -= entry.heap_size();
+        self.start = entry.seq;
+        self.current -= 1;
+        self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        self.slot.emit(Signal::EntryEvicted);
+    }
+
+    /// Pops off the commands older than the max age among the first `max` commands,
+    /// and returns the number of commands that were removed.
+    fn evict_expired(&mut self, max: usize) -> usize {
+        let max_age = match self.max_age {
+            Some(max_age) => max_age,
+            None => return 0,
+        };
+        let now = self.clock.now();
+        let expired = self
+            .entries
+            .iter()
+            .take(max)
+            .take_while(|entry| now.duration_since(&entry.timestamp) > max_age)
+            .count();
+        for _ in 0..expired {
+            self.evict();
+        }
+        expired
+    }
+
+    /// Removes the commands that are older than the max age set in the builder.
+    ///
+    /// Only the commands before the current position are removed,
+    /// so the commands that can be redone are kept.
+    pub fn prune(&mut self) {
+        self.__prune();
+    }
+
+    pub(crate) fn __prune(&mut self) -> usize {
+        let old = self.current();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let evicted = self.evict_expired(old);
+        self.emit_changes(old, could_undo, could_redo, was_saved);
+        evicted
     }
 
     /// Calls the [`undo`] method for the active command and sets
@@ -271,16 +601,26 @@ impl<C: Command, F: FnMut(Signal)> Record<C, F> {
     /// If an error occur when executing [`undo`] the error is returned.
     ///
     /// [`undo`]: ../trait.Command.html#tymethod.undo
-    pub fn undo(&mut self) -> Result<C> {
+    pub fn undo(&mut self) -> Result<(), Error<C::Error>> {
         if !self.can_undo() {
             return Ok(());
         }
         let was_saved = self.is_saved();
         let old = self.current();
-        self.entries[self.current - 1].undo(&mut self.target)?;
+        let entry = &mut self.entries[self.current - 1];
+        let heap_size = entry.heap_size();
+        let result = entry.undo(&mut self.target);
+        // The command can change when it is undone, so its heap size is counted again.
+        self.heap_size = self.heap_size - heap_size + entry.heap_size();
+        result.map_err(|error| Error::new(Op::Undo, At::new(0, old), error))?;
         self.current -= 1;
         let len = self.len();
         let is_saved = self.is_saved();
+        self.slot.emit(Signal::Undone(self.current));
+        self.slot.emit(Signal::CurrentChanged {
+            old,
+            new: self.current,
+        });
         self.slot.emit_if(old == len, Signal::Redo(true));
         self.slot.emit_if(old == 1, Signal::Undo(false));
         self.slot
@@ -295,16 +635,25 @@ impl<C: Command, F: FnMut(Signal)> Record<C, F> {
     /// If an error occur when applying [`redo`] the error is returned.
     ///
     /// [`redo`]: trait.Command.html#method.redo
-    pub fn redo(&mut self) -> Result<C> {
+    pub fn redo(&mut self) -> Result<(), Error<C::Error>> {
         if !self.can_redo() {
             return Ok(());
         }
         let was_saved = self.is_saved();
         let old = self.current();
-        self.entries[self.current].redo(&mut self.target)?;
+        let entry = &mut self.entries[self.current];
+        let heap_size = entry.heap_size();
+        let result = entry.redo(&mut self.target);
+        self.heap_size = self.heap_size - heap_size + entry.heap_size();
+        result.map_err(|error| Error::new(Op::Redo, At::new(0, old), error))?;
         self.current += 1;
         let len = self.len();
         let is_saved = self.is_saved();
+        self.slot.emit(Signal::Redone(old));
+        self.slot.emit(Signal::CurrentChanged {
+            old,
+            new: self.current,
+        });
         self.slot.emit_if(old == len - 1, Signal::Redo(false));
         self.slot.emit_if(old == 0, Signal::Undo(true));
         self.slot
@@ -315,63 +664,124 @@ impl<C: Command, F: FnMut(Signal)> Record<C, F> {
     /// Repeatedly calls [`undo`] or [`redo`] until the command at `current` is reached.
     ///
     /// # Errors
-    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned,
+    /// together with the number of steps that were completed.
     ///
     /// [`undo`]: trait.Command.html#tymethod.undo
     /// [`redo`]: trait.Command.html#method.redo
-    pub fn go_to(&mut self, current: usize) -> Option<Result<C>> {
+    pub fn go_to(&mut self, current: usize) -> Option<Result<(), Error<C::Error>>> {
         if current > self.len() {
             return None;
         }
+        let old = self.current();
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         let was_saved = self.is_saved();
         // Temporarily remove slot so they are not called each iteration.
-        let f = self.slot.f.take();
+        let slot = mem::take(&mut self.slot);
+        let result = self.walk_to(current);
+        // Add slot back.
+        self.slot = slot;
+        self.emit_changes(old, could_undo, could_redo, was_saved);
+        Some(result)
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the command at `current` is reached.
+    ///
+    /// Unlike [`go_to`], the record is restored to the position it started at if an error occurs.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`], the record goes back to
+    /// the starting position and the error is returned in [`Rollback::Restored`].
+    /// If an error also occur when going back, both errors are returned in [`Rollback::Failed`]
+    /// together with the position the record ended up at.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`Rollback::Restored`]: ../enum.Rollback.html#variant.Restored
+    /// [`Rollback::Failed`]: ../enum.Rollback.html#variant.Failed
+    pub fn go_to_atomic(
+        &mut self,
+        current: usize,
+    ) -> Option<Result<(), Rollback<Error<C::Error>>>> {
+        if current > self.len() {
+            return None;
+        }
+        let old = self.current();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        // Temporarily remove slot so they are not called each iteration.
+        let slot = mem::take(&mut self.slot);
+        let result = self
+            .walk_to(current)
+            .map_err(|error| match self.walk_to(old) {
+                Ok(()) => Rollback::Restored(error),
+                Err(rollback) => Rollback::Failed {
+                    error,
+                    rollback,
+                    at: At::new(0, self.current()),
+                },
+            });
+        // Add slot back.
+        self.slot = slot;
+        self.emit_changes(old, could_undo, could_redo, was_saved);
+        Some(result)
+    }
+
+    fn walk_to(&mut self, current: usize) -> Result<(), Error<C::Error>> {
         // Decide if we need to undo or redo to reach current.
         let apply = if current > self.current() {
             Record::redo
         } else {
             Record::undo
         };
+        let mut steps = 0;
         while self.current() != current {
-            if let Err(err) = apply(self) {
-                self.slot.f = f;
-                return Some(Err(err));
-            }
+            apply(self).map_err(|error| error.with_steps(steps))?;
+            steps += 1;
         }
-        // Add slot back.
-        self.slot.f = f;
+        Ok(())
+    }
+
+    pub(crate) fn emit_changes(
+        &mut self,
+        old: usize,
+        could_undo: bool,
+        could_redo: bool,
+        was_saved: bool,
+    ) {
+        let current = self.current();
         let can_undo = self.can_undo();
         let can_redo = self.can_redo();
         let is_saved = self.is_saved();
+        self.slot
+            .emit_if(old != current, Signal::CurrentChanged { old, new: current });
         self.slot
             .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
         self.slot
             .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
         self.slot
             .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
-        Some(Ok(()))
     }
 
-    /// Go back or forward in the record to the command that was made closest to the datetime provided.
-    #[cfg(feature = "chrono")]
-    pub fn time_travel(&mut self, to: &DateTime<impl TimeZone>) -> Option<Result<C>> {
-        let to = to.with_timezone(&Utc);
+    /// Go back or forward in the record to the command that was made closest to the timestamp provided.
+    pub fn time_travel(&mut self, to: &K::Timestamp) -> Option<Result<(), Error<C::Error>>> {
         let current = match self.entries.as_slices() {
             ([], []) => return None,
-            (start, []) => match start.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
+            (start, []) => match start.binary_search_by(|entry| entry.timestamp.cmp(to)) {
                 Ok(current) | Err(current) => current,
             },
-            ([], end) => match end.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
+            ([], end) => match end.binary_search_by(|entry| entry.timestamp.cmp(to)) {
                 Ok(current) | Err(current) => current,
             },
-            (start, end) => match start.last().unwrap().timestamp.cmp(&to) {
-                Ordering::Less => match start.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
+            (start, end) => match start.last().unwrap().timestamp.cmp(to) {
+                Ordering::Less => match start.binary_search_by(|entry| entry.timestamp.cmp(to)) {
                     Ok(current) | Err(current) => current,
                 },
                 Ordering::Equal => start.len(),
-                Ordering::Greater => match end.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
+                Ordering::Greater => match end.binary_search_by(|entry| entry.timestamp.cmp(to)) {
                     Ok(current) | Err(current) => start.len() + current,
                 },
             },
@@ -380,7 +790,7 @@ impl<C: Command, F: FnMut(Signal)> Record<C, F> {
     }
 }
 
-impl<C: Command + ToString, F> Record<C, F> {
+impl<C: Command + ToString, F, K: Clock> Record<C, F, K> {
     /// Returns the string of the command which will be undone in the next call to [`undo`].
     ///
     /// [`undo`]: struct.Record.html#method.undo
@@ -413,16 +823,18 @@ where
     }
 }
 
-impl<C: Command, F: FnMut(Signal)> From<History<C, F>> for Record<C, F> {
-    fn from(history: History<C, F>) -> Record<C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> From<History<C, F, K>> for Record<C, F, K> {
+    fn from(history: History<C, F, K>) -> Record<C, F, K> {
         history.record
     }
 }
 
-impl<C: Command, F> fmt::Debug for Record<C, F>
+impl<C: Command, F, K: Clock> fmt::Debug for Record<C, F, K>
 where
     C: fmt::Debug,
     C::Target: fmt::Debug,
+    K: fmt::Debug,
+    K::Timestamp: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Record")
@@ -430,8 +842,15 @@ where
             .field("target", &self.target)
             .field("current", &self.current)
             .field("limit", &self.limit)
+            .field("budget", &self.budget)
+            .field("max_age", &self.max_age)
+            .field("merge_window", &self.merge_window)
             .field("saved", &self.saved)
             .field("slot", &self.slot)
+            .field("clock", &self.clock)
+            .field("start", &self.start)
+            .field("seq", &self.seq)
+            .field("heap_size", &self.heap_size)
             .finish()
     }
 }
@@ -439,10 +858,20 @@ where
 /// Builder for a record.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug)]
-pub struct Builder {
+pub struct Builder<K = DefaultClock> {
     capacity: usize,
     limit: NonZeroUsize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    budget: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    max_age: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    merge_window: Option<Duration>,
     saved: bool,
+    #[cfg_attr(feature = "serde", serde(default = "crate::enabled"))]
+    events: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    clock: K,
 }
 
 impl Builder {
@@ -450,13 +879,20 @@ impl Builder {
     pub fn new() -> Builder {
         Builder {
             capacity: 0,
-            limit: NonZeroUsize::new(usize::max_value()).unwrap(),
+            limit: NonZeroUsize::new(usize::MAX).unwrap(),
+            budget: None,
+            max_age: None,
+            merge_window: None,
             saved: true,
+            events: true,
+            clock: DefaultClock::default(),
         }
     }
+}
 
+impl<K: Clock> Builder<K> {
     /// Sets the capacity for the record.
-    pub fn capacity(&mut self, capacity: usize) -> &mut Builder {
+    pub fn capacity(&mut self, capacity: usize) -> &mut Builder<K> {
         self.capacity = capacity;
         self
     }
@@ -465,54 +901,136 @@ impl Builder {
     ///
     /// # Panics
     /// Panics if `limit` is `0`.
-    pub fn limit(&mut self, limit: usize) -> &mut Builder {
+    pub fn limit(&mut self, limit: usize) -> &mut Builder<K> {
         self.limit = NonZeroUsize::new(limit).expect("limit can not be `0`");
         self
     }
 
+    /// Sets the heap size `budget` of the record in bytes.
+    ///
+    /// When the sum of the [`heap_size`] of the commands exceeds the budget,
+    /// the oldest commands are removed until it fits, but the newest command is always kept.
+    /// By default the record has no budget.
+    ///
+    /// [`heap_size`]: ../trait.Command.html#method.heap_size
+    pub fn budget(&mut self, budget: usize) -> &mut Builder<K> {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets the max age of the commands in the record.
+    ///
+    /// The commands that are older than the max age are removed when a new command is applied,
+    /// or when [`prune`] is called. By default the commands are kept regardless of their age.
+    ///
+    /// [`prune`]: struct.Record.html#method.prune
+    pub fn max_age(&mut self, max_age: Duration) -> &mut Builder<K> {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the merge window of the record.
+    ///
+    /// A command is only merged with the last command if it is applied within the window
+    /// after the last command was applied or merged into. This makes a burst of
+    /// commands, like typing, a single step that is undone and redone together.
+    /// By default the commands are merged regardless of how long ago the last command was applied.
+    pub fn merge_window(&mut self, window: Duration) -> &mut Builder<K> {
+        self.merge_window = Some(window);
+        self
+    }
+
+    /// Sets the clock that time stamps the commands in the record.
+    ///
+    /// By default the [`DefaultClock`] is used.
+    ///
+    /// [`DefaultClock`]: ../type.DefaultClock.html
+    pub fn clock<L: Clock>(&self, clock: L) -> Builder<L> {
+        Builder {
+            capacity: self.capacity,
+            limit: self.limit,
+            budget: self.budget,
+            max_age: self.max_age,
+            merge_window: self.merge_window,
+            saved: self.saved,
+            events: self.events,
+            clock,
+        }
+    }
+
     /// Sets if the target is initially in a saved state.
     /// By default the target is in a saved state.
-    pub fn saved(&mut self, saved: bool) -> &mut Builder {
+    pub fn saved(&mut self, saved: bool) -> &mut Builder<K> {
         self.saved = saved;
         self
     }
 
+    /// Sets if the slot receives the signals that report changes, like `Applied` and `Merged`.
+    /// If off, only the `Undo`, `Redo` and `Saved` signals are emitted.
+    /// By default all signals are emitted.
+    pub fn events(&mut self, on: bool) -> &mut Builder<K> {
+        self.events = on;
+        self
+    }
+
     /// Builds the record.
-    pub fn build<C: Command>(&self, target: C::Target) -> Record<C> {
+    pub fn build<C: Command>(&self, target: C::Target) -> Record<C, fn(Signal), K>
+    where
+        K: Clone,
+    {
         Record {
             entries: VecDeque::with_capacity(self.capacity),
             target,
             current: 0,
             limit: self.limit,
+            budget: self.budget,
+            max_age: self.max_age,
+            merge_window: self.merge_window,
             saved: if self.saved { Some(0) } else { None },
-            slot: Slot::default(),
+            slot: Slot::new(None, self.events),
+            clock: self.clock.clone(),
+            start: 0,
+            seq: 1,
+            heap_size: 0,
         }
     }
 
     /// Builds the record with the slot.
-    pub fn build_with<C: Command, F>(&self, target: C::Target, slot: F) -> Record<C, F> {
+    pub fn build_with<C: Command, F>(&self, target: C::Target, slot: F) -> Record<C, F, K>
+    where
+        K: Clone,
+    {
         Record {
             entries: VecDeque::with_capacity(self.capacity),
             target,
             current: 0,
             limit: self.limit,
+            budget: self.budget,
+            max_age: self.max_age,
+            merge_window: self.merge_window,
             saved: if self.saved { Some(0) } else { None },
-            slot: Slot { f: Some(slot) },
+            slot: Slot::new(Some(slot), self.events),
+            clock: self.clock.clone(),
+            start: 0,
+            seq: 1,
+            heap_size: 0,
         }
     }
 
     /// Creates the record with a default `target`.
-    pub fn default<C: Command>(&self) -> Record<C>
+    pub fn default<C: Command>(&self) -> Record<C, fn(Signal), K>
     where
         C::Target: Default,
+        K: Clone,
     {
         self.build(Default::default())
     }
 
     /// Creates the record with a default `target` and with the slot.
-    pub fn default_with<C: Command, F>(&self, slot: F) -> Record<C, F>
+    pub fn default_with<C: Command, F>(&self, slot: F) -> Record<C, F, K>
     where
         C::Target: Default,
+        K: Clone,
     {
         self.build_with(Default::default(), slot)
     }
@@ -540,6 +1058,7 @@ enum QueueCommand<C> {
 /// # impl Command for Add {
 /// #     type Target = String;
 /// #     type Error = &'static str;
+/// #     type Output = ();
 /// #     fn apply(&mut self, s: &mut String) -> redo::Result<Add> {
 /// #         s.push(self.0);
 /// #         Ok(())
@@ -549,7 +1068,7 @@ enum QueueCommand<C> {
 /// #         Ok(())
 /// #     }
 /// # }
-/// # fn main() -> redo::Result<Add> {
+/// # fn main() -> Result<(), redo::Error<&'static str>> {
 /// let mut record = Record::default();
 /// let mut queue = record.queue();
 /// queue.apply(Add('a'));
@@ -561,12 +1080,12 @@ enum QueueCommand<C> {
 /// # Ok(())
 /// # }
 /// ```
-pub struct Queue<'a, C: Command, F> {
-    record: &'a mut Record<C, F>,
+pub struct Queue<'a, C: Command, F, K: Clock = DefaultClock> {
+    record: &'a mut Record<C, F, K>,
     commands: Vec<QueueCommand<C>>,
 }
 
-impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> Queue<'_, C, F, K> {
     /// Queues an `apply` action.
     pub fn apply(&mut self, command: C) {
         self.commands.push(QueueCommand::Apply(command));
@@ -582,17 +1101,19 @@ impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
         self.commands.push(QueueCommand::Redo);
     }
 
-    /// Applies the queued commands.
+    /// Applies the queued commands. The outputs of the commands are discarded.
     ///
     /// # Errors
-    /// If an error occurs, it stops applying the commands and returns the error.
-    pub fn commit(self) -> Result<C> {
-        for command in self.commands {
+    /// If an error occurs, it stops applying the commands and returns the error,
+    /// together with the number of commands that were applied.
+    pub fn commit(self) -> Result<(), Error<C::Error>> {
+        for (steps, command) in self.commands.into_iter().enumerate() {
             match command {
-                QueueCommand::Apply(command) => self.record.apply(command)?,
-                QueueCommand::Undo => self.record.undo()?,
-                QueueCommand::Redo => self.record.redo()?,
+                QueueCommand::Apply(command) => self.record.apply(command).map(|_| ()),
+                QueueCommand::Undo => self.record.undo(),
+                QueueCommand::Redo => self.record.redo(),
             }
+            .map_err(|error| error.with_steps(steps))?;
         }
         Ok(())
     }
@@ -601,12 +1122,12 @@ impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
     pub fn cancel(self) {}
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<C, F> {
+    pub fn queue(&mut self) -> Queue<'_, C, F, K> {
         self.record.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<C, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, C, F, K> {
         self.record.checkpoint()
     }
 
@@ -616,8 +1137,8 @@ impl<C: Command, F: FnMut(Signal)> Queue<'_, C, F> {
     }
 }
 
-impl<'a, C: Command, F> From<&'a mut Record<C, F>> for Queue<'a, C, F> {
-    fn from(record: &'a mut Record<C, F>) -> Self {
+impl<'a, C: Command, F, K: Clock> From<&'a mut Record<C, F, K>> for Queue<'a, C, F, K> {
+    fn from(record: &'a mut Record<C, F, K>) -> Self {
         Queue {
             record,
             commands: Vec::new(),
@@ -626,29 +1147,35 @@ impl<'a, C: Command, F> From<&'a mut Record<C, F>> for Queue<'a, C, F> {
 }
 
 #[derive(Debug)]
-enum CheckpointCommand<C> {
-    Apply(Option<usize>, VecDeque<Entry<C>>),
+enum CheckpointCommand<C, T> {
+    Apply(Option<usize>, Outcome, usize, VecDeque<Entry<C, T>>),
     Undo,
     Redo,
 }
 
 /// Wraps a record and gives it checkpoint functionality.
-pub struct Checkpoint<'a, C: Command, F> {
-    record: &'a mut Record<C, F>,
-    commands: Vec<CheckpointCommand<C>>,
+pub struct Checkpoint<'a, C: Command, F, K: Clock = DefaultClock> {
+    record: &'a mut Record<C, F, K>,
+    commands: Vec<CheckpointCommand<C, K::Timestamp>>,
+    seq: usize,
 }
 
-impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
+impl<C: Command, F: FnMut(Signal), K: Clock> Checkpoint<'_, C, F, K> {
     /// Calls the `apply` method.
-    pub fn apply(&mut self, command: C) -> Result<C> {
+    ///
+    /// The command is not merged into a command that was applied before the checkpoint,
+    /// since that command could not be restored if the checkpoint is canceled.
+    pub fn apply(&mut self, command: C) -> Result<(C::Output, Outcome), Error<C::Error>> {
         let saved = self.record.saved;
-        let (_, tail) = self.record.__apply(command)?;
-        self.commands.push(CheckpointCommand::Apply(saved, tail));
-        Ok(())
+        let merge = self.record.applied_since(self.seq);
+        let (output, outcome, evicted, tail) = self.record.__apply(command, merge)?;
+        self.commands
+            .push(CheckpointCommand::Apply(saved, outcome, evicted, tail));
+        Ok((output, outcome))
     }
 
     /// Calls the `undo` method.
-    pub fn undo(&mut self) -> Result<C> {
+    pub fn undo(&mut self) -> Result<(), Error<C::Error>> {
         if self.record.can_undo() {
             self.record.undo()?;
             self.commands.push(CheckpointCommand::Undo);
@@ -657,7 +1184,7 @@ impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
     }
 
     /// Calls the `redo` method.
-    pub fn redo(&mut self) -> Result<C> {
+    pub fn redo(&mut self) -> Result<(), Error<C::Error>> {
         if self.record.can_redo() {
             self.record.redo()?;
             self.commands.push(CheckpointCommand::Redo);
@@ -672,30 +1199,43 @@ impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
     ///
     /// # Errors
     /// If an error occur when canceling the changes, the error is returned
+    /// together with the number of commands that were canceled,
     /// and the remaining commands are not canceled.
-    pub fn cancel(self) -> Result<C> {
-        for command in self.commands.into_iter().rev() {
+    pub fn cancel(self) -> Result<(), Error<C::Error>> {
+        for (steps, command) in self.commands.into_iter().rev().enumerate() {
+            let with_steps = |error: Error<C::Error>| error.with_steps(steps);
             match command {
-                CheckpointCommand::Apply(saved, mut entries) => {
-                    self.record.undo()?;
-                    self.record.entries.pop_back();
-                    self.record.entries.append(&mut entries);
-                    self.record.saved = saved;
+                CheckpointCommand::Apply(saved, outcome, evicted, mut entries) => {
+                    match outcome {
+                        Outcome::Pushed(_) | Outcome::Evicted(_) => {
+                            self.record.undo().map_err(with_steps)?;
+                            self.record.pop_back();
+                            self.record.append(&mut entries);
+                        }
+                        // The command it was merged into is removed when its own apply is canceled.
+                        Outcome::Merged(_) => self.record.append(&mut entries),
+                        // Put back the annulled command, which is the first of the entries.
+                        Outcome::Annulled => {
+                            self.record.append(&mut entries);
+                            self.record.redo().map_err(with_steps)?;
+                        }
+                    }
+                    self.record.saved = saved.and_then(|saved| saved.checked_sub(evicted));
                 }
-                CheckpointCommand::Undo => self.record.redo()?,
-                CheckpointCommand::Redo => self.record.undo()?,
+                CheckpointCommand::Undo => self.record.redo().map_err(with_steps)?,
+                CheckpointCommand::Redo => self.record.undo().map_err(with_steps)?,
             }
         }
         Ok(())
     }
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<C, F> {
+    pub fn queue(&mut self) -> Queue<'_, C, F, K> {
         self.record.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<C, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, C, F, K> {
         self.record.checkpoint()
     }
 
@@ -705,23 +1245,168 @@ impl<C: Command, F: FnMut(Signal)> Checkpoint<'_, C, F> {
     }
 }
 
-impl<'a, C: Command, F> From<&'a mut Record<C, F>> for Checkpoint<'a, C, F> {
-    fn from(record: &'a mut Record<C, F>) -> Self {
+impl<'a, C: Command, F, K: Clock> From<&'a mut Record<C, F, K>> for Checkpoint<'a, C, F, K> {
+    fn from(record: &'a mut Record<C, F, K>) -> Self {
         Checkpoint {
+            seq: record.seq,
             record,
             commands: Vec::new(),
         }
     }
 }
 
+/// Iterator over the commands in a record, or in a branch of a history.
+///
+/// This struct is created by the [`iter`](struct.Record.html#method.iter) method.
+#[derive(Debug)]
+pub struct Iter<'a, C, T> {
+    entries: Enumerate<vec_deque::Iter<'a, Entry<C, T>>>,
+    offset: usize,
+    current: usize,
+    saved: Option<usize>,
+}
+
+impl<C, T> Clone for Iter<'_, C, T> {
+    fn clone(&self) -> Self {
+        Iter {
+            entries: self.entries.clone(),
+            offset: self.offset,
+            current: self.current,
+            saved: self.saved,
+        }
+    }
+}
+
+impl<'a, C, T> Iter<'a, C, T> {
+    /// Returns an iterator over the `entries` that starts at position `offset`.
+    pub(crate) fn new(
+        entries: &'a VecDeque<Entry<C, T>>,
+        offset: usize,
+        current: usize,
+        saved: Option<usize>,
+    ) -> Iter<'a, C, T> {
+        Iter {
+            entries: entries.iter().enumerate(),
+            offset,
+            current,
+            saved,
+        }
+    }
+}
+
+impl<'a, C, T> Iterator for Iter<'a, C, T> {
+    type Item = View<'a, C, T>;
+
+    fn next(&mut self) -> Option<View<'a, C, T>> {
+        let (index, entry) = self.entries.next()?;
+        Some(View::new(
+            self.offset + index,
+            entry,
+            self.current,
+            self.saved,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<C, T> DoubleEndedIterator for Iter<'_, C, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (index, entry) = self.entries.next_back()?;
+        Some(View::new(
+            self.offset + index,
+            entry,
+            self.current,
+            self.saved,
+        ))
+    }
+}
+
+impl<C, T> ExactSizeIterator for Iter<'_, C, T> {}
+
+impl<C, T> FusedIterator for Iter<'_, C, T> {}
+
+/// A read-only view of a command in the record or history.
+#[derive(Debug)]
+pub struct View<'a, C, T> {
+    index: usize,
+    entry: &'a Entry<C, T>,
+    applied: bool,
+    saved: bool,
+}
+
+impl<'a, C, T> View<'a, C, T> {
+    pub(crate) fn new(
+        index: usize,
+        entry: &'a Entry<C, T>,
+        current: usize,
+        saved: Option<usize>,
+    ) -> View<'a, C, T> {
+        View {
+            index,
+            entry,
+            applied: index < current,
+            saved: saved == Some(index + 1),
+        }
+    }
+
+    /// Returns the index of the command.
+    ///
+    /// Going to position `index + 1` leaves the target as it was right after this command was applied.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns a reference to the command.
+    pub fn command(&self) -> &'a C {
+        &self.entry.command
+    }
+
+    /// Returns the identifier of the state right after the command.
+    pub fn node(&self) -> NodeId {
+        NodeId(self.entry.seq)
+    }
+
+    /// Returns `true` if the command is before the current position and is applied to the target,
+    /// `false` if it is after the current position and can be redone.
+    pub fn is_applied(&self) -> bool {
+        self.applied
+    }
+
+    /// Returns `true` if the target is marked as saved right after this command.
+    pub fn is_saved(&self) -> bool {
+        self.saved
+    }
+
+    /// Returns the time when the command was applied, or last merged into.
+    pub fn timestamp(&self) -> T
+    where
+        T: Copy,
+    {
+        self.entry.timestamp
+    }
+}
+
+impl<C, T> Clone for View<'_, C, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C, T> Copy for View<'_, C, T> {}
+
 /// Configurable display formatting for record.
 #[derive(Copy, Clone)]
-pub struct Display<'a, C: Command, F> {
-    record: &'a Record<C, F>,
-    format: crate::format::Format,
+pub struct Display<'a, C: Command, F, K: Clock = DefaultClock> {
+    record: &'a Record<C, F, K>,
+    format: Format<'a>,
+    time: TimeFormat<'a, K::Timestamp>,
+    utc: Option<bool>,
 }
 
-impl<C: Command, F: FnMut(Signal)> Display<'_, C, F> {
+impl<'a, C: Command, F: FnMut(Signal), K: Clock> Display<'a, C, F, K> {
     /// Show colored output (on by default).
     ///
     /// Requires the `colored` feature to be enabled.
@@ -754,18 +1439,96 @@ impl<C: Command, F: FnMut(Signal)> Display<'_, C, F> {
         self.format.saved = on;
         self
     }
+
+    /// Show only the commands at most `n` positions away from the current position.
+    ///
+    /// The commands that are not shown are summarized, like `… 420 more …`.
+    pub fn window(&mut self, n: usize) -> &mut Self {
+        self.format.set_window(self.record.current(), n);
+        self
+    }
+
+    /// Show only the commands at the positions in the range.
+    ///
+    /// The commands that are not shown are summarized, like `… 420 more …`.
+    pub fn range(&mut self, range: impl RangeBounds<usize>) -> &mut Self {
+        self.format.set_range(range);
+        self
+    }
+
+    /// Sets the symbols, labels and colors of the output.
+    pub fn style(&mut self, style: Style<'a>) -> &mut Self {
+        self.format.style = style;
+        self
+    }
+
+    /// Formats the timestamps by using the function.
+    ///
+    /// The timestamps are formatted by using [`Timestamp::fmt`] by default.
+    ///
+    /// [`Timestamp::fmt`]: ../trait.Timestamp.html#method.fmt
+    pub fn timestamp(
+        &mut self,
+        format: fn(&K::Timestamp, &mut fmt::Formatter) -> fmt::Result,
+    ) -> &mut Self {
+        self.time = TimeFormat::Function(format);
+        self
+    }
+
+    /// Formats the timestamps with a strftime-style pattern, like `%H:%M:%S`.
+    ///
+    /// The pattern is ignored by timestamps without a calendar date and time,
+    /// see [`Timestamp::fmt_with`]. The timestamps are formatted as by default
+    /// if the pattern is not valid.
+    ///
+    /// [`Timestamp::fmt_with`]: ../trait.Timestamp.html#method.fmt_with
+    pub fn pattern(&mut self, pattern: &'a str) -> &mut Self {
+        self.time = TimeFormat::Pattern(pattern);
+        self
+    }
+
+    /// Formats the timestamps as the time elapsed until `now`, like `3 minutes ago`.
+    ///
+    /// Timestamps later than `now` are shown as `0 seconds ago`, and timestamps
+    /// that are formatted as nothing by [`Timestamp::fmt`], like `()`, are not shown.
+    ///
+    /// [`Timestamp::fmt`]: ../trait.Timestamp.html#method.fmt
+    pub fn relative(&mut self, now: K::Timestamp) -> &mut Self {
+        self.time = TimeFormat::Relative(now);
+        self
+    }
+
+    /// Show the timestamps in UTC if `on` is `true`, or in the local time zone if it is `false`.
+    ///
+    /// By default the timestamps are shown as formatted by [`Timestamp::fmt`]
+    /// and the patterns are formatted in the local time zone.
+    ///
+    /// [`Timestamp::fmt`]: ../trait.Timestamp.html#method.fmt
+    pub fn utc(&mut self, on: bool) -> &mut Self {
+        self.utc = Some(on);
+        self
+    }
 }
 
-impl<C: Command + fmt::Display, F> Display<'_, C, F> {
-    fn fmt_list(&self, f: &mut fmt::Formatter, at: At, entry: Option<&Entry<C>>) -> fmt::Result {
+impl<C: Command + fmt::Display, F, K: Clock> Display<'_, C, F, K> {
+    fn fmt_list(
+        &self,
+        f: &mut fmt::Formatter,
+        at: At,
+        entry: Option<&Entry<C, K::Timestamp>>,
+    ) -> fmt::Result {
         self.format.position(f, at, false)?;
 
-        #[cfg(feature = "chrono")]
-        {
-            if let Some(entry) = entry {
-                if self.format.detailed {
-                    self.format.timestamp(f, &entry.timestamp)?;
-                }
+        if let Some(entry) = entry {
+            if self.format.detailed {
+                self.format.timestamp(
+                    f,
+                    &Formatted {
+                        timestamp: &entry.timestamp,
+                        format: self.time,
+                        utc: self.utc,
+                    },
+                )?;
             }
         }
 
@@ -789,35 +1552,108 @@ impl<C: Command + fmt::Display, F> Display<'_, C, F> {
     }
 }
 
-impl<'a, C: Command, F> From<&'a Record<C, F>> for Display<'a, C, F> {
-    fn from(record: &'a Record<C, F>) -> Self {
+impl<'a, C: Command, F, K: Clock> From<&'a Record<C, F, K>> for Display<'a, C, F, K> {
+    fn from(record: &'a Record<C, F, K>) -> Self {
         Display {
             record,
             format: Format::default(),
+            time: TimeFormat::Default,
+            utc: None,
         }
     }
 }
 
-impl<C: Command + fmt::Display, F> fmt::Display for Display<'_, C, F> {
+impl<C: Command + fmt::Display, F, K: Clock> fmt::Display for Display<'_, C, F, K> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (i, entry) in self.record.entries.iter().enumerate().rev() {
-            let at = At::new(0, i + 1);
-            self.fmt_list(f, at, Some(entry))?;
+        let mut elided = 0;
+        for i in (0..=self.record.len()).rev() {
+            let at = At::new(0, i);
+            if !self.format.is_visible(at) {
+                // The start of the record is not a command, so it is not counted.
+                elided += usize::from(i > 0);
+                continue;
+            }
+            if elided > 0 {
+                self.format.elided(f, elided)?;
+                writeln!(f)?;
+                elided = 0;
+            }
+            let entry = i.checked_sub(1).map(|i| &self.record.entries[i]);
+            self.fmt_list(f, at, entry)?;
         }
-        self.fmt_list(f, At::new(0, 0), None)
+        if elided > 0 {
+            self.format.elided(f, elided)?;
+        }
+        Ok(())
+    }
+}
+
+/// JSON export of the record.
+///
+/// Contains the same information as the display structure, without requiring the commands or
+/// the target to be serializable. The commands are written by using their `Display` implementation
+/// and the timestamps are written by [`Timestamp::fmt_json`], as RFC 3339 strings in UTC
+/// for the wall-clock timestamps, as nanoseconds for `Duration`, and as `null` without a clock.
+///
+/// ```json
+/// {
+///   "current": 1,
+///   "saved": null,
+///   "entries": [{ "position": 1, "node": 1, "timestamp": null, "command": "Add a" }]
+/// }
+/// ```
+///
+/// [`Timestamp::fmt_json`]: ../trait.Timestamp.html#method.fmt_json
+#[derive(Copy, Clone)]
+pub struct Json<'a, C: Command, F, K: Clock> {
+    record: &'a Record<C, F, K>,
+}
+
+impl<'a, C: Command, F, K: Clock> From<&'a Record<C, F, K>> for Json<'a, C, F, K> {
+    fn from(record: &'a Record<C, F, K>) -> Self {
+        Json { record }
+    }
+}
+
+impl<C: Command + fmt::Display, F, K: Clock> fmt::Display for Json<'_, C, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{\"current\":{},\"saved\":", self.record.current)?;
+        match self.record.saved {
+            Some(saved) => write!(f, "{}", saved)?,
+            None => f.write_str("null")?,
+        }
+        f.write_str(",\"entries\":[")?;
+        for (i, entry) in self.record.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_char(',')?;
+            }
+            format::json_entry(f, i + 1, entry)?;
+        }
+        f.write_str("]}")
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::*;
-    use alloc::string::String;
-
+    use crate::{
+        fixtures::{Edit, Spend},
+        *,
+    };
+    use alloc::{
+        boxed::Box,
+        rc::Rc,
+        string::{String, ToString},
+        vec::Vec,
+    };
+    use core::{cell::RefCell, fmt, time::Duration};
+
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     struct Add(char);
 
     impl Command for Add {
         type Target = String;
         type Error = &'static str;
+        type Output = ();
 
         fn apply(&mut self, s: &mut String) -> Result<Add> {
             s.push(self.0);
@@ -830,6 +1666,105 @@ mod tests {
         }
     }
 
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Add {}", self.0)
+        }
+    }
+
+    #[test]
+    fn output() {
+        let mut record = Record::default();
+        assert_eq!(
+            record.apply(Edit::Push("a".into())),
+            Ok((1, Outcome::Pushed(0)))
+        );
+        assert_eq!(
+            record.apply(Edit::Push("b".into())),
+            Ok((2, Outcome::Pushed(1)))
+        );
+        record.undo().unwrap();
+        record.redo().unwrap();
+        assert_eq!(record.target(), "ab");
+        assert_eq!(
+            record.checkpoint().apply(Edit::Push("c".into())),
+            Ok((3, Outcome::Pushed(2)))
+        );
+        assert_eq!(
+            record.apply(Edit::Pop("c".into())),
+            Ok((2, Outcome::Annulled))
+        );
+    }
+
+    #[test]
+    fn annul() {
+        let mut record = Record::default();
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.apply(Edit::Push("b".into())).unwrap();
+        assert_eq!(
+            record.apply(Edit::Pop("b".into())),
+            Ok((1, Outcome::Annulled))
+        );
+        // Both commands are gone, so the current position moves back past them.
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+        assert!(!record.can_redo());
+        record.undo().unwrap();
+        assert_eq!(record.target(), "");
+        assert!(!record.can_undo());
+    }
+
+    #[test]
+    fn budget() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut record = record::Builder::new()
+            .budget(5)
+            .build_with(String::new(), move |signal| slot.borrow_mut().push(signal));
+        record.apply(Edit::Push("ab".into())).unwrap();
+        record.apply(Edit::Push("cd".into())).unwrap();
+        signals.borrow_mut().clear();
+        assert_eq!(
+            record.apply(Edit::Push("ef".into())),
+            Ok((6, Outcome::Evicted(1)))
+        );
+        assert_eq!(
+            *signals.borrow(),
+            [Signal::Applied(2), Signal::EntryEvicted]
+        );
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.heap_size(), 4);
+        // The newest command is kept even if it does not fit.
+        record.apply(Edit::Push("123456".into())).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.heap_size(), 6);
+        record.undo().unwrap();
+        assert_eq!(record.target(), "abcdef");
+    }
+
+    #[test]
+    fn heap_size() {
+        let mut record = Record::new(String::new());
+        record.apply(Edit::Type("ab".into())).unwrap();
+        record.apply(Edit::Type("c".into())).unwrap();
+        assert_eq!(record.heap_size(), 3);
+        record.apply(Edit::Push("de".into())).unwrap();
+        record.apply(Edit::Pop("de".into())).unwrap();
+        assert_eq!(record.heap_size(), 3);
+        record.apply(Edit::Push("fg".into())).unwrap();
+        record.undo().unwrap();
+        assert_eq!(record.heap_size(), 5);
+        record.apply(Edit::Push("h".into())).unwrap();
+        assert_eq!(record.heap_size(), 4);
+        let mut checkpoint = record.checkpoint();
+        checkpoint.undo().unwrap();
+        checkpoint.apply(Edit::Push("ijkl".into())).unwrap();
+        checkpoint.cancel().unwrap();
+        assert_eq!(record.heap_size(), 4);
+        record.clear();
+        assert_eq!(record.heap_size(), 0);
+    }
+
     #[test]
     fn go_to() {
         let mut record = Record::default();
@@ -861,6 +1796,446 @@ mod tests {
         assert_eq!(record.current(), 3);
     }
 
+    #[test]
+    fn go_to_atomic() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.target_mut().truncate(1);
+        let error = Error::new(Op::Undo, At::new(0, 2), "s is empty").with_steps(1);
+        assert_eq!(
+            record.go_to_atomic(0).unwrap(),
+            Err(Rollback::Restored(error))
+        );
+        assert_eq!(record.current(), 3);
+        assert_eq!(record.target(), "a");
+        assert_eq!(record.go_to(0).unwrap(), Err(error));
+        assert_eq!(record.current(), 2);
+        assert!(record.go_to_atomic(4).is_none());
+    }
+
+    #[test]
+    fn go_to_atomic_failed() {
+        let mut record = Record::new(3);
+        record.apply(Spend).unwrap();
+        record.apply(Spend).unwrap();
+        record.apply(Spend).unwrap();
+        // There is only fuel for two of the steps back, and none for going forward again.
+        *record.target_mut() = 2;
+        assert_eq!(
+            record.go_to_atomic(0).unwrap(),
+            Err(Rollback::Failed {
+                error: Error::new(Op::Undo, At::new(0, 1), "out of fuel").with_steps(2),
+                rollback: Error::new(Op::Redo, At::new(0, 1), "out of fuel"),
+                at: At::new(0, 1),
+            })
+        );
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.target(), &0);
+    }
+
+    #[test]
+    fn error() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.target_mut().truncate(1);
+        let mut queue = record.queue();
+        queue.undo();
+        queue.undo();
+        queue.undo();
+        let error = queue.commit().unwrap_err();
+        assert_eq!(error.op(), Op::Undo);
+        assert_eq!(error.at(), At::new(0, 2));
+        assert_eq!(error.steps(), 1);
+        assert_eq!(error.into_error(), "s is empty");
+        assert_eq!(
+            error.to_string(),
+            "undo failed at 0:2 after 1 step: s is empty"
+        );
+        assert_eq!(record.current(), 2);
+    }
+
+    #[test]
+    fn iter() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.set_saved(true);
+        record.apply(Add('c')).unwrap();
+        record.undo().unwrap();
+        let mut iter = record.iter();
+        assert_eq!(iter.len(), 3);
+        let a = iter.next().unwrap();
+        assert_eq!((a.index(), a.command().0), (0, 'a'));
+        assert!(a.is_applied() && !a.is_saved());
+        let b = iter.next().unwrap();
+        assert_eq!((b.index(), b.command().0), (1, 'b'));
+        assert!(b.is_applied() && b.is_saved());
+        let c = iter.next().unwrap();
+        assert_eq!((c.index(), c.command().0), (2, 'c'));
+        assert!(!c.is_applied() && !c.is_saved());
+        assert!(iter.next().is_none());
+        // The iterator can be cloned even though the commands can not.
+        let mut iter = record.iter();
+        iter.next();
+        assert_eq!(iter.clone().count(), 2);
+        assert_eq!(iter.count(), 2);
+    }
+
+    #[test]
+    fn signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = signals.clone();
+        let mut record = record::Builder::new()
+            .limit(2)
+            .build_with(String::new(), move |signal| slot.borrow_mut().push(signal));
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.undo().unwrap();
+        record.go_to(0).unwrap().unwrap();
+        record.clear();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Applied(0),
+                Signal::CurrentChanged { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Saved(false),
+                Signal::Applied(1),
+                Signal::CurrentChanged { old: 1, new: 2 },
+                Signal::EntryEvicted,
+                Signal::Applied(1),
+                Signal::Undone(1),
+                Signal::CurrentChanged { old: 2, new: 1 },
+                Signal::Redo(true),
+                Signal::CurrentChanged { old: 1, new: 0 },
+                Signal::Undo(false),
+                Signal::Cleared,
+                Signal::Redo(false),
+            ]
+        );
+
+        let slot = signals.clone();
+        let mut record = record::Builder::new()
+            .events(false)
+            .build_with(String::new(), move |signal| slot.borrow_mut().push(signal));
+        record.apply(Add('a')).unwrap();
+        record.undo().unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Undo(true),
+                Signal::Saved(false),
+                Signal::Redo(true),
+                Signal::Undo(false),
+                Signal::Saved(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn subscribe() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let mut record: Record<Add, Box<dyn FnMut(Signal)>> = record::Builder::new()
+            .events(false)
+            .default_with(Box::new(|_| ()));
+        let slot = signals.clone();
+        let a = record.subscribe(Box::new(move |signal| {
+            slot.borrow_mut().push(('a', signal))
+        }));
+        let slot = signals.clone();
+        let b = record.subscribe(Box::new(move |signal| {
+            slot.borrow_mut().push(('b', signal))
+        }));
+        record.apply(Add('a')).unwrap();
+        assert!(record.unsubscribe(a).is_some());
+        assert!(record.unsubscribe(a).is_none());
+        record.undo().unwrap();
+        assert!(record.unsubscribe(b).is_some());
+        record.redo().unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                ('a', Signal::Undo(true)),
+                ('b', Signal::Undo(true)),
+                ('a', Signal::Saved(false)),
+                ('b', Signal::Saved(false)),
+                ('b', Signal::Redo(true)),
+                ('b', Signal::Undo(false)),
+                ('b', Signal::Saved(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let mut record = record::Builder::new()
+            .saved(false)
+            .max_age(Duration::from_secs(60))
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.set_saved(true);
+        record.clock_mut().advance(Duration::from_secs(30));
+        record.apply(Add('c')).unwrap();
+        record.undo().unwrap();
+        record.clock_mut().advance(Duration::from_secs(60));
+        record.prune();
+        // The command that can be redone is kept.
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 0);
+        assert!(record.is_saved());
+        record.redo().unwrap();
+        assert_eq!(record.target(), "abc");
+        // The newest command is kept when applying.
+        record.clock_mut().advance(Duration::from_secs(1));
+        record.apply(Add('d')).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+    }
+
+    #[test]
+    fn merge_window() {
+        let mut record = record::Builder::new()
+            .saved(false)
+            .merge_window(Duration::from_secs(1))
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        record.apply(Edit::Type("a".into())).unwrap();
+        record.clock_mut().advance(Duration::from_millis(800));
+        assert_eq!(
+            record.apply(Edit::Type("b".into())),
+            Ok((2, Outcome::Merged(0)))
+        );
+        // The window starts over when a command is merged.
+        record.clock_mut().advance(Duration::from_millis(800));
+        assert_eq!(
+            record.apply(Edit::Type("c".into())),
+            Ok((3, Outcome::Merged(0)))
+        );
+        assert_eq!(
+            record.iter().next().unwrap().timestamp(),
+            Duration::from_millis(1600)
+        );
+        record.clock_mut().advance(Duration::from_secs(2));
+        assert_eq!(
+            record.apply(Edit::Type("d".into())),
+            Ok((4, Outcome::Pushed(1)))
+        );
+        record.undo().unwrap();
+        assert_eq!(record.target(), "abc");
+
+        // Without a merge window the merged command keeps the time it was first applied.
+        let mut record = record::Builder::new()
+            .saved(false)
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        record.apply(Edit::Type("a".into())).unwrap();
+        record.clock_mut().advance(Duration::from_secs(5));
+        assert_eq!(
+            record.apply(Edit::Type("b".into())),
+            Ok((2, Outcome::Merged(0)))
+        );
+        assert_eq!(
+            record.iter().next().unwrap().timestamp(),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn time_travel() {
+        let mut record = record::Builder::new()
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(1));
+        record.apply(Add('b')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(1));
+        record.apply(Add('c')).unwrap();
+        record
+            .time_travel(&Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.target(), "a");
+        record
+            .time_travel(&Duration::from_secs(3))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.target(), "abc");
+        record
+            .time_travel(&Duration::from_secs(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.target(), "");
+    }
+
+    #[test]
+    fn window() {
+        let mut record = Record::default();
+        for c in "abcdefghij".chars() {
+            record.apply(Add(c)).unwrap();
+        }
+        record.go_to(5).unwrap().unwrap();
+        let mut display = record.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        display.detailed(false).window(1);
+        assert_eq!(
+            display.to_string(),
+            "… 4 more …\n6 Add f\n5 (current) Add e\n4 Add d\n… 3 more …"
+        );
+        display.range(..2);
+        assert_eq!(display.to_string(), "… 9 more …\n1 Add a\n0 (saved)");
+    }
+
+    #[test]
+    fn relative_time() {
+        let mut record = record::Builder::new()
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(3600));
+        record.apply(Add('b')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(150));
+        record.apply(Add('c')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(1));
+        let now = record.clock_mut().now();
+        let mut display = record.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        display.current(false).saved(false).relative(now);
+        assert_eq!(
+            display.to_string(),
+            "3 1 second ago\nAdd c\n\
+             2 2 minutes ago\nAdd b\n\
+             1 1 hour ago\nAdd a\n\
+             0"
+        );
+
+        // Timestamps without a time are not shown.
+        let mut record = record::Builder::new().clock(()).default();
+        record.apply(Add('a')).unwrap();
+        let mut display = record.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        display.current(false).saved(false).relative(());
+        assert_eq!(display.to_string(), "1\nAdd a\n0");
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_clock() {
+        use time::OffsetDateTime;
+
+        let start = OffsetDateTime::from_unix_timestamp(1_614_834_367).unwrap();
+        let mut record = record::Builder::new()
+            .clock(ManualClock::new(start))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(90));
+        record.apply(Add('b')).unwrap();
+        record
+            .time_travel(&(start + Duration::from_secs(90)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.target(), "a");
+        let view = record.iter().next_back().unwrap();
+        assert_eq!(
+            view.timestamp().duration_since(&start),
+            Duration::from_secs(90)
+        );
+        let mut display = record.display();
+        #[cfg(feature = "colored")]
+        display.colored(false);
+        display.utc(true);
+        assert_eq!(
+            display.to_string(),
+            "2 Thu, 04 Mar 2021 05:07:37 +0000\nAdd b\n\
+             1 Thu, 04 Mar 2021 05:06:07 +0000 (current)\nAdd a\n\
+             0 (saved)"
+        );
+        display.pattern("%H:%M:%S");
+        assert_eq!(
+            display.to_string(),
+            "2 05:07:37\nAdd b\n\
+             1 05:06:07 (current)\nAdd a\n\
+             0 (saved)"
+        );
+        // A pattern that is not valid falls back to the default format.
+        display.pattern("%Q");
+        assert_eq!(
+            display.to_string(),
+            "2 Thu, 04 Mar 2021 05:07:37 +0000\nAdd b\n\
+             1 Thu, 04 Mar 2021 05:06:07 +0000 (current)\nAdd a\n\
+             0 (saved)"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut record = record::Builder::new()
+            .clock(ManualClock::new(Duration::from_secs(0)))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.clock_mut().advance(Duration::from_secs(1));
+        record.apply(Add('"')).unwrap();
+        record.undo().unwrap();
+        assert_eq!(
+            record.json().to_string(),
+            r#"{"current":1,"saved":0,"entries":["#.to_string()
+                + r#"{"position":1,"node":1,"timestamp":0,"command":"Add a"},"#
+                + r#"{"position":2,"node":2,"timestamp":1000000000,"command":"Add \""}]}"#
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn json_chrono() {
+        use chrono::{DateTime, Utc};
+
+        let start = DateTime::<Utc>::from_timestamp(1_614_834_367, 0).unwrap();
+        let mut record = record::Builder::new()
+            .clock(ManualClock::new(start))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.clock_mut().advance(Duration::from_millis(1500));
+        record.apply(Add('b')).unwrap();
+        assert_eq!(
+            record.json().to_string(),
+            r#"{"current":2,"saved":0,"entries":["#.to_string()
+                + r#"{"position":1,"node":1,"timestamp":"2021-03-04T05:06:07Z","command":"Add a"},"#
+                + r#"{"position":2,"node":2,"timestamp":"2021-03-04T05:06:08.500Z","command":"Add b"}]}"#
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn json_time() {
+        use time::{OffsetDateTime, UtcOffset};
+
+        // The timestamps are written in UTC whatever their offset is.
+        let start = OffsetDateTime::from_unix_timestamp(1_614_834_367)
+            .unwrap()
+            .to_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        let mut record = record::Builder::new()
+            .clock(ManualClock::new(start))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.clock_mut().advance(Duration::from_millis(1500));
+        record.apply(Add('b')).unwrap();
+        assert_eq!(
+            record.json().to_string(),
+            r#"{"current":2,"saved":0,"entries":["#.to_string()
+                + r#"{"position":1,"node":1,"timestamp":"2021-03-04T05:06:07Z","command":"Add a"},"#
+                + r#"{"position":2,"node":2,"timestamp":"2021-03-04T05:06:08.5Z","command":"Add b"}]}"#
+        );
+    }
+
     #[test]
     fn queue_commit() {
         let mut record = Record::default();
@@ -956,4 +2331,96 @@ mod tests {
         assert!(record.is_saved());
         assert_eq!(record.target(), "abc");
     }
+
+    #[test]
+    fn checkpoint_merged() {
+        let mut record = Record::default();
+        record.apply(Edit::Type("a".into())).unwrap();
+        record.apply(Edit::Push("x".into())).unwrap();
+        record.undo().unwrap();
+        let mut cp = record.checkpoint();
+        // The command is not merged into the command applied before the checkpoint.
+        assert_eq!(
+            cp.apply(Edit::Type("b".into())),
+            Ok((2, Outcome::Pushed(1)))
+        );
+        cp.apply(Edit::Push("y".into())).unwrap();
+        cp.undo().unwrap();
+        assert_eq!(
+            cp.apply(Edit::Type("c".into())),
+            Ok((3, Outcome::Merged(1)))
+        );
+        cp.cancel().unwrap();
+        assert_eq!(record.target(), "a");
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.len(), 2);
+        record.redo().unwrap();
+        assert_eq!(record.target(), "ax");
+    }
+
+    #[test]
+    fn checkpoint_annulled() {
+        let mut record = Record::default();
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.apply(Edit::Push("b".into())).unwrap();
+        record.undo().unwrap();
+        let mut cp = record.checkpoint();
+        // The command applied before the checkpoint is not annulled.
+        assert_eq!(cp.apply(Edit::Pop("a".into())), Ok((0, Outcome::Pushed(1))));
+        cp.apply(Edit::Push("c".into())).unwrap();
+        cp.apply(Edit::Push("d".into())).unwrap();
+        cp.undo().unwrap();
+        assert_eq!(cp.apply(Edit::Pop("c".into())), Ok((0, Outcome::Annulled)));
+        cp.apply(Edit::Push("e".into())).unwrap();
+        assert_eq!(cp.apply(Edit::Pop("e".into())), Ok((0, Outcome::Annulled)));
+        cp.cancel().unwrap();
+        assert_eq!(record.target(), "a");
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.len(), 2);
+        record.redo().unwrap();
+        assert_eq!(record.target(), "ab");
+    }
+
+    #[test]
+    fn checkpoint_evicted() {
+        let mut record = record::Builder::new().limit(2).build(String::new());
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.apply(Edit::Push("b".into())).unwrap();
+        record.set_saved(true);
+        let mut cp = record.checkpoint();
+        assert_eq!(
+            cp.apply(Edit::Push("c".into())),
+            Ok((3, Outcome::Evicted(1)))
+        );
+        cp.cancel().unwrap();
+        // The evicted command is gone, but the saved state is still the current one.
+        assert_eq!(record.target(), "ab");
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.len(), 1);
+        assert!(record.is_saved());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_old() {
+        // Serialized by version 0.41 without chrono, so the commands have no timestamps.
+        let json = r#"{"entries":[{"command":"a"},{"command":"b"}],"target":"ab","current":2,"limit":10,"saved":2,"slot":{}}"#;
+        let record: Record<Add> = serde_json::from_str(json).unwrap();
+        assert!(record.is_saved());
+        assert!(record
+            .iter()
+            .all(|view| view.timestamp() == <DefaultClock as Clock>::Timestamp::epoch()));
+        let json = serde_json::to_string(&record).unwrap();
+        let mut record: Record<Add> = serde_json::from_str(&json).unwrap();
+        assert_eq!(record.len(), 2);
+        assert!(record.is_saved());
+        record.undo().unwrap();
+        assert_eq!(record.target(), "a");
+        record.apply(Add('c')).unwrap();
+        assert_eq!(record.target(), "ac");
+        // The commands are numbered when read, so each state gets its own node identifier.
+        for current in 0..=record.len() {
+            assert_eq!(record.position(record.node_at(current)), Some(current));
+        }
+    }
 }